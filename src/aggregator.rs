@@ -0,0 +1,668 @@
+use crate::{
+    arbitrage::ArbitrageTracker,
+    binance::{Binance, BinanceCaptureFrames, BinanceStream},
+    bitstamp::Bitstamp,
+    coinbase::Coinbase,
+    connection::{Connection, ConnectionState, ConnectionStates},
+    exchange::{get_order_book, Exchange, ExchangeSettings},
+    instrument::Instrument,
+    kraken::Kraken,
+    merged_book::{BookTimes, MergedBook, TakerFees},
+    metrics,
+    order_book::{to_f64, OrderBook},
+    proto::ArbitrageEvent,
+    recorder::Recorder,
+    replay::Replay,
+    synthetic::SyntheticPair,
+};
+use std::{
+    collections::HashMap,
+    sync::{
+        self,
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::{
+    select, spawn,
+    sync::{broadcast, broadcast::error::RecvError, mpsc, Mutex},
+    task::JoinHandle,
+    time::{self, interval},
+};
+use tracing::{info, info_span, trace, Instrument as _};
+
+pub const CHANNEL_BUFFER_SIZE: usize = 100;
+const DEFAULT_STALENESS_TIMEOUT: Duration = Duration::from_secs(10);
+const STALENESS_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+// how long an exchange book is merged after its last update
+#[derive(Clone)]
+pub struct StalenessTimeouts {
+    default: Duration,
+    exchanges: HashMap<String, Duration>,
+}
+
+impl Default for StalenessTimeouts {
+    fn default() -> Self {
+        StalenessTimeouts::new(DEFAULT_STALENESS_TIMEOUT)
+    }
+}
+
+impl StalenessTimeouts {
+    pub fn new(default: Duration) -> Self {
+        Self {
+            default,
+            exchanges: HashMap::new(),
+        }
+    }
+    pub fn with_exchange(mut self, exchange: String, timeout: Duration) -> Self {
+        self.exchanges.insert(exchange, timeout);
+        self
+    }
+    fn is_stale(&self, order_book: &OrderBook, now: Instant) -> bool {
+        let timeout = self
+            .exchanges
+            .get(order_book.get_exchange_name())
+            .unwrap_or(&self.default);
+        now.duration_since(order_book.get_received_at()) > *timeout
+    }
+}
+
+// how the exchanges of a pair are connected and merged
+#[derive(Clone)]
+pub struct AggregatorSettings {
+    pub binance_stream: BinanceStream,
+    pub staleness_timeouts: StalenessTimeouts,
+    pub taker_fees: TakerFees,
+    // exchanges without settings use their defaults
+    pub exchanges: HashMap<String, ExchangeSettings>,
+    // capacity of the channels between the exchanges, the merged book and the subscribers
+    pub channel_buffer_size: usize,
+    // captures the raw frames of binance and bitstamp when set
+    pub recorder: Option<Recorder>,
+    // plays captured frames instead of connecting the exchanges when set
+    pub replay: Option<Replay>,
+}
+
+impl Default for AggregatorSettings {
+    fn default() -> Self {
+        Self {
+            binance_stream: BinanceStream::default(),
+            staleness_timeouts: StalenessTimeouts::default(),
+            taker_fees: TakerFees::default(),
+            exchanges: HashMap::new(),
+            channel_buffer_size: CHANNEL_BUFFER_SIZE,
+            recorder: None,
+            replay: None,
+        }
+    }
+}
+
+impl AggregatorSettings {
+    // the symbol and settings of an exchange, none when it doesn't list the pair or is disabled
+    fn get_exchange<X: Exchange>(
+        &self,
+        instrument: &Instrument,
+    ) -> Option<(String, ExchangeSettings)> {
+        let settings = self
+            .exchanges
+            .get(X::get_name())
+            .cloned()
+            .unwrap_or_else(X::get_default_settings);
+        let symbol = instrument.get_symbol(X::get_name())?;
+        settings.enabled.then(|| (symbol.to_string(), settings))
+    }
+}
+
+// the exchange tasks and merged book of a single pair, only running while it has subscribers
+pub struct PairAggregator {
+    pair: String,
+    instrument: Instrument,
+    settings: AggregatorSettings,
+    // keeps counting when the pair is restarted so sequences never go back
+    sequence: Arc<AtomicU64>,
+    running: sync::Mutex<Option<Running>>,
+    // implied from the books of two other pairs instead of exchange books
+    synthetic: Option<(SyntheticPair, [Arc<PairAggregator>; 2])>,
+}
+
+struct Running {
+    merged_book_tx: broadcast::Sender<Arc<MergedBook>>,
+    arbitrage_tx: broadcast::Sender<ArbitrageEvent>,
+    latest_merged_book: Arc<sync::RwLock<Option<Arc<MergedBook>>>>,
+    connection_states: ConnectionStates,
+    subscribers: usize,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+impl PairAggregator {
+    pub fn new(instrument: Instrument, settings: AggregatorSettings) -> Self {
+        Self {
+            pair: instrument.get_pair(),
+            instrument,
+            settings,
+            sequence: Arc::new(AtomicU64::new(0)),
+            running: sync::Mutex::new(None),
+            synthetic: None,
+        }
+    }
+    // the legs keep running while the synthetic pair has subscribers
+    pub fn new_synthetic(
+        synthetic_pair: SyntheticPair,
+        legs: [Arc<PairAggregator>; 2],
+        settings: AggregatorSettings,
+    ) -> Self {
+        Self {
+            synthetic: Some((synthetic_pair.clone(), legs)),
+            ..Self::new(synthetic_pair.get_instrument(), settings)
+        }
+    }
+    pub fn get_pair(&self) -> &str {
+        &self.pair
+    }
+    pub fn subscribe(self: &Arc<Self>) -> Subscription {
+        let ((snapshot, merged_book_rx), guard) = self.add_subscriber(|running| {
+            // subscribing before reading the snapshot can repeat a merged book but never miss one
+            let merged_book_rx = running.merged_book_tx.subscribe();
+            let snapshot = running.latest_merged_book.read().unwrap().clone();
+            (snapshot, merged_book_rx)
+        });
+        Subscription {
+            pair: self.pair.clone(),
+            snapshot,
+            merged_book_rx,
+            _guard: Some(guard),
+        }
+    }
+    pub fn subscribe_arbitrage(self: &Arc<Self>) -> ArbitrageSubscription {
+        let (arbitrage_rx, guard) = self.add_subscriber(|running| running.arbitrage_tx.subscribe());
+        ArbitrageSubscription {
+            pair: self.pair.clone(),
+            arbitrage_rx,
+            _guard: guard,
+        }
+    }
+    // the first subscriber connects the exchanges
+    fn add_subscriber<T>(
+        self: &Arc<Self>,
+        subscribe: impl FnOnce(&Running) -> T,
+    ) -> (T, SubscriberGuard) {
+        let mut running = self.running.lock().unwrap();
+        let running = running.get_or_insert_with(|| self.start());
+        running.subscribers += 1;
+        (subscribe(running), SubscriberGuard(self.clone()))
+    }
+    // the last subscriber leaving disconnects the exchanges
+    fn unsubscribe(&self) {
+        let mut running = self.running.lock().unwrap();
+        if let Some(state) = running.as_mut() {
+            state.subscribers -= 1;
+            if state.subscribers == 0 {
+                *running = None;
+                info!(pair = %self.pair, "no subscribers left, exchanges disconnected");
+            }
+        }
+    }
+    // the latest merged book, a pair nobody streams is connected until its first merged book or
+    // the timeout, none when no book came in time
+    pub async fn get_current_merged_book(
+        self: &Arc<Self>,
+        timeout: Duration,
+    ) -> Option<Arc<MergedBook>> {
+        // the subscription keeps the pair running for the call
+        let mut subscription = self.subscribe();
+        let first_merged_book = async {
+            loop {
+                match subscription.recv().await {
+                    Ok(merged_book) => return Some(merged_book),
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        };
+        time::timeout(timeout, first_merged_book)
+            .await
+            .ok()
+            .flatten()
+    }
+    pub fn get_connection_states(&self) -> Vec<(&'static str, ConnectionState)> {
+        self.running
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|running| running.connection_states.get_all())
+            .unwrap_or_default()
+    }
+    fn start(&self) -> Running {
+        if let Some((synthetic_pair, legs)) = &self.synthetic {
+            return self.start_synthetic(synthetic_pair.clone(), legs);
+        }
+        info!(pair = %self.pair, "first subscriber, connecting exchanges");
+
+        let exchanges: Arc<Mutex<HashMap<&str, OrderBook>>> = Arc::new(Mutex::new(HashMap::new()));
+        let connection_states = ConnectionStates::default();
+
+        let buffer_size = self.settings.channel_buffer_size;
+        let (order_book_tx, mut order_book_rx) = mpsc::channel::<OrderBook>(buffer_size);
+        let (merged_book_tx, _merged_book_rx) = broadcast::channel::<Arc<MergedBook>>(buffer_size);
+        let (arbitrage_tx, _arbitrage_rx) = broadcast::channel::<ArbitrageEvent>(buffer_size);
+
+        let connection = |exchange: &'static str| {
+            Connection::new(exchange, order_book_tx.clone(), connection_states.clone())
+                .with_pair(&self.pair)
+        };
+        let recorded_connection = |exchange: &'static str| {
+            connection(exchange).with_recorder(self.settings.recorder.clone())
+        };
+
+        let settings = &self.settings;
+        let mut tasks = vec![];
+        if let Some(replay) = &settings.replay {
+            // the captured frames of the enabled exchanges listing the pair stand in for them
+            let mut session = replay.session(&self.pair);
+            if settings.get_exchange::<Binance>(&self.instrument).is_some() {
+                // captures of both binance streams are told apart by their frames
+                let mut frames = BinanceCaptureFrames::default();
+                session = session.with_parser(
+                    Binance::get_name(),
+                    connection(Binance::get_name()),
+                    move |source, frame| frames.parse_frame(source, frame),
+                );
+            }
+            if settings
+                .get_exchange::<Bitstamp>(&self.instrument)
+                .is_some()
+            {
+                session = session.with_exchange::<Bitstamp>(connection(Bitstamp::get_name()));
+            }
+            tasks.push(spawn(session.run()));
+        } else {
+            // only the enabled exchanges listing the pair are connected
+            if let Some((symbol, exchange)) = settings.get_exchange::<Bitstamp>(&self.instrument) {
+                tasks.push(spawn(get_order_book::<Bitstamp>(
+                    symbol,
+                    exchange,
+                    recorded_connection(Bitstamp::get_name()),
+                )));
+            }
+            if let Some((symbol, exchange)) = settings.get_exchange::<Coinbase>(&self.instrument) {
+                tasks.push(spawn(get_order_book::<Coinbase>(
+                    symbol,
+                    exchange,
+                    connection(Coinbase::get_name()),
+                )));
+            }
+            if let Some((symbol, exchange)) = settings.get_exchange::<Kraken>(&self.instrument) {
+                tasks.push(spawn(get_order_book::<Kraken>(
+                    symbol,
+                    exchange,
+                    connection(Kraken::get_name()),
+                )));
+            }
+            if let Some((symbol, exchange)) = settings.get_exchange::<Binance>(&self.instrument) {
+                tasks.push(match settings.binance_stream {
+                    BinanceStream::Partial => spawn(get_order_book::<Binance>(
+                        symbol,
+                        exchange,
+                        recorded_connection(Binance::get_name()),
+                    )),
+                    BinanceStream::Diff => spawn(Binance::get_diff_order_book(
+                        symbol,
+                        exchange,
+                        recorded_connection(Binance::get_name()),
+                    )),
+                });
+            }
+        }
+
+        let latest_merged_book = Arc::new(sync::RwLock::new(None));
+        let latest_merged_book_clone = latest_merged_book.clone();
+        let merged_book_tx_clone = merged_book_tx.clone();
+        let arbitrage_tx_clone = arbitrage_tx.clone();
+        let mut arbitrage_tracker = ArbitrageTracker::new(self.pair.clone());
+        let staleness_timeouts = settings.staleness_timeouts.clone();
+        let taker_fees = settings.taker_fees.clone();
+        let sequence = self.sequence.clone();
+        let pair = self.pair.clone();
+        // replayed books keep the event times of the capture, their latency means nothing
+        let records_latency = settings.replay.is_none();
+
+        tasks.push(spawn(
+            async move {
+                let mut staleness_check = interval(STALENESS_CHECK_INTERVAL);
+                let mut stale_exchanges = vec![];
+                loop {
+                    let (trigger_exchange, event_time) = select! {
+                        order_book = order_book_rx.recv() => match order_book {
+                            Some(order_book) => {
+                                let exchange = order_book.get_exchange_name();
+                                let event_time = order_book.get_event_time();
+                                update_exchange(&exchanges, order_book).await;
+                                (Some(exchange), event_time)
+                            }
+                            None => break,
+                        },
+                        // an exchange going stale changes the summary even without updates
+                        _ = staleness_check.tick() => {
+                            if get_stale_exchanges(&exchanges, &staleness_timeouts).await
+                                == stale_exchanges
+                            {
+                                continue;
+                            }
+                            (None, None)
+                        }
+                    };
+
+                    let merged_book = get_merged_book(&exchanges, &staleness_timeouts)
+                        .await
+                        .with_taker_fees(&taker_fees)
+                        .with_sequence(
+                            sequence.fetch_add(1, Ordering::Relaxed) + 1,
+                            trigger_exchange,
+                        );
+                    let merged_book = Arc::new(merged_book);
+                    stale_exchanges = merged_book.get_stale_exchanges().to_vec();
+                    *latest_merged_book_clone.write().unwrap() = Some(merged_book.clone());
+
+                    for event in arbitrage_tracker.update(&merged_book, Instant::now()) {
+                        // nobody listening for opportunities is fine
+                        let _ = arbitrage_tx_clone.send(event);
+                    }
+
+                    if let Some(spread) = merged_book.get_spread() {
+                        metrics::set_spread(&pair, to_f64(spread));
+                    }
+                    let sent = merged_book_tx_clone.send(merged_book).is_ok();
+                    // exchanges not saying when they produced their book are left out
+                    if let (true, Some(exchange), Some(event_time)) =
+                        (records_latency, trigger_exchange, event_time)
+                    {
+                        if let Ok(latency) = SystemTime::now().duration_since(event_time) {
+                            metrics::record_event_to_broadcast(exchange, &pair, latency);
+                        }
+                    }
+                    if sent {
+                        trace!("merged book sent");
+                    }
+                }
+            }
+            .instrument(info_span!("aggregator", pair = %self.pair)),
+        ));
+
+        Running {
+            merged_book_tx,
+            arbitrage_tx,
+            latest_merged_book,
+            connection_states,
+            subscribers: 0,
+            tasks,
+        }
+    }
+    fn start_synthetic(
+        &self,
+        synthetic_pair: SyntheticPair,
+        legs: &[Arc<PairAggregator>; 2],
+    ) -> Running {
+        info!(pair = %self.pair, "first subscriber, subscribing to the legs");
+
+        let buffer_size = self.settings.channel_buffer_size;
+        let (merged_book_tx, _merged_book_rx) = broadcast::channel::<Arc<MergedBook>>(buffer_size);
+        let (arbitrage_tx, _arbitrage_rx) = broadcast::channel::<ArbitrageEvent>(buffer_size);
+        let latest_merged_book = Arc::new(sync::RwLock::new(None));
+
+        // the subscriptions live in the task, aborting it unsubscribes from the legs
+        let mut first = legs[0].subscribe();
+        let mut second = legs[1].subscribe();
+        let latest_merged_book_clone = latest_merged_book.clone();
+        let merged_book_tx_clone = merged_book_tx.clone();
+        let sequence = self.sequence.clone();
+        let pair = self.pair.clone();
+
+        let task = spawn(
+            async move {
+                let (mut first_book, mut second_book) = (None, None);
+                loop {
+                    let result = select! {
+                        result = first.recv() => result.map(|book| first_book = Some(book)),
+                        result = second.recv() => result.map(|book| second_book = Some(book)),
+                    };
+                    match result {
+                        Ok(()) => {}
+                        // the next leg book replaces the skipped ones
+                        Err(RecvError::Lagged(skipped)) => {
+                            metrics::record_lagged("synthetic", &pair, skipped);
+                            continue;
+                        }
+                        Err(RecvError::Closed) => break,
+                    }
+                    let (Some(first_book), Some(second_book)) = (&first_book, &second_book) else {
+                        continue;
+                    };
+
+                    let order_book = synthetic_pair.get_order_book(first_book, second_book);
+                    let mut stale_exchanges: Vec<String> = first_book
+                        .get_stale_exchanges()
+                        .iter()
+                        .chain(second_book.get_stale_exchanges())
+                        .cloned()
+                        .collect();
+                    stale_exchanges.sort();
+                    stale_exchanges.dedup();
+
+                    let merged_book = MergedBook::new([&order_book].into_iter(), stale_exchanges)
+                        .with_sequence(sequence.fetch_add(1, Ordering::Relaxed) + 1, None);
+                    let merged_book = Arc::new(merged_book);
+                    *latest_merged_book_clone.write().unwrap() = Some(merged_book.clone());
+                    let _ = merged_book_tx_clone.send(merged_book);
+                }
+            }
+            .instrument(info_span!("synthetic", pair = %self.pair)),
+        );
+
+        Running {
+            merged_book_tx,
+            arbitrage_tx,
+            latest_merged_book,
+            connection_states: ConnectionStates::default(),
+            subscribers: 0,
+            tasks: vec![task],
+        }
+    }
+}
+
+// keeps a pair running until dropped
+struct SubscriberGuard(Arc<PairAggregator>);
+
+impl Drop for SubscriberGuard {
+    fn drop(&mut self) {
+        self.0.unsubscribe();
+    }
+}
+
+// the merged books of a pair
+pub struct Subscription {
+    pair: String,
+    snapshot: Option<Arc<MergedBook>>,
+    merged_book_rx: broadcast::Receiver<Arc<MergedBook>>,
+    _guard: Option<SubscriberGuard>,
+}
+
+impl Subscription {
+    #[cfg(test)]
+    pub fn from_receiver(merged_book_rx: broadcast::Receiver<Arc<MergedBook>>) -> Self {
+        Self {
+            pair: String::new(),
+            snapshot: None,
+            merged_book_rx,
+            _guard: None,
+        }
+    }
+    pub fn get_pair(&self) -> &str {
+        &self.pair
+    }
+    // the merged book at subscription time comes first
+    pub async fn recv(&mut self) -> Result<Arc<MergedBook>, RecvError> {
+        match self.snapshot.take() {
+            Some(snapshot) => Ok(snapshot),
+            None => self.merged_book_rx.recv().await,
+        }
+    }
+}
+
+// the arbitrage opportunities of a pair
+pub struct ArbitrageSubscription {
+    pair: String,
+    arbitrage_rx: broadcast::Receiver<ArbitrageEvent>,
+    _guard: SubscriberGuard,
+}
+
+impl ArbitrageSubscription {
+    pub fn get_pair(&self) -> &str {
+        &self.pair
+    }
+    pub async fn recv(&mut self) -> Result<ArbitrageEvent, RecvError> {
+        self.arbitrage_rx.recv().await
+    }
+}
+
+async fn update_exchange(exchanges: &Arc<Mutex<HashMap<&str, OrderBook>>>, order_book: OrderBook) {
+    let mut exchanges = exchanges.lock().await;
+    let order_book_exchange_name = order_book.get_exchange_name();
+    // if key exists, update value to avoid cloning
+    if exchanges.contains_key(order_book_exchange_name) {
+        exchanges
+            .entry(order_book_exchange_name)
+            .and_modify(|e| *e = order_book);
+    } else {
+        exchanges
+            .entry(order_book_exchange_name)
+            .or_insert(order_book);
+    }
+}
+
+async fn get_stale_exchanges(
+    exchanges: &Arc<Mutex<HashMap<&'static str, OrderBook>>>,
+    staleness_timeouts: &StalenessTimeouts,
+) -> Vec<String> {
+    let now = Instant::now();
+    let mut stale_exchanges: Vec<String> = exchanges
+        .lock()
+        .await
+        .values()
+        .filter(|order_book| staleness_timeouts.is_stale(order_book, now))
+        .map(|order_book| order_book.get_exchange_name().into())
+        .collect();
+    stale_exchanges.sort();
+    stale_exchanges
+}
+
+async fn get_merged_book(
+    exchanges: &Arc<Mutex<HashMap<&'static str, OrderBook>>>,
+    staleness_timeouts: &StalenessTimeouts,
+) -> MergedBook {
+    let exchanges = exchanges.lock().await;
+    let now = Instant::now();
+
+    // a stale book would keep dead prices in the summary, possibly crossing the others
+    let (stale_order_books, fresh_order_books): (Vec<&OrderBook>, Vec<&OrderBook>) = exchanges
+        .values()
+        .partition(|order_book| staleness_timeouts.is_stale(order_book, now));
+
+    MergedBook::new(
+        fresh_order_books.into_iter(),
+        stale_order_books
+            .into_iter()
+            .map(|order_book| order_book.get_exchange_name().into())
+            .collect(),
+    )
+    .with_book_times(exchanges.values().map(BookTimes::from).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        proto::SummaryRequest,
+        test_data::{get_binance_order_book_builder, get_bitstamp_order_book_builder},
+    };
+
+    #[tokio::test]
+    async fn test_get_summary() {
+        let exchanges_mutex = Arc::new(Mutex::new(HashMap::new()));
+        update_exchange(&exchanges_mutex, get_bitstamp_order_book_builder().build()).await;
+        update_exchange(&exchanges_mutex, get_binance_order_book_builder().build()).await;
+
+        let summary = get_merged_book(&exchanges_mutex, &StalenessTimeouts::default())
+            .await
+            .get_summary(&SummaryRequest::default());
+        assert_eq!(summary.spread, 1e-6);
+        assert_eq!(summary.exact_spread, "0.000001");
+
+        assert_eq!(summary.bids.len(), 10);
+        assert_eq!(summary.asks.len(), 10);
+
+        // highest price first for bids
+        assert_eq!(summary.bids[0].price, 0.068426);
+        assert_eq!(summary.bids[9].price, 0.06842);
+
+        // lowest price first for asks
+        assert_eq!(summary.asks[0].price, 0.068427);
+        assert_eq!(summary.asks[9].price, 0.068437);
+
+        // test that bids with price 0.06842268 are sorted by amount
+        assert!(summary.bids[3].price == summary.bids[4].price);
+        assert!(summary.bids[3].amount > summary.bids[4].amount);
+
+        assert!(summary.stale_exchanges.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_current_merged_book_without_exchanges() {
+        // no exchange lists the pair, so no merged book ever comes
+        let aggregator = Arc::new(PairAggregator::new(
+            Instrument::new("eth", "btc"),
+            AggregatorSettings::default(),
+        ));
+        let merged_book = aggregator
+            .get_current_merged_book(Duration::from_millis(50))
+            .await;
+        assert!(merged_book.is_none());
+        // the pair is disconnected again after the call
+        assert!(aggregator.running.lock().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_summary_without_stale_exchanges() {
+        let exchanges_mutex = Arc::new(Mutex::new(HashMap::new()));
+
+        let mut bitstamp_order_book = get_bitstamp_order_book_builder().build();
+        bitstamp_order_book.set_received_at(Instant::now() - Duration::from_secs(5));
+        update_exchange(&exchanges_mutex, bitstamp_order_book).await;
+        update_exchange(&exchanges_mutex, get_binance_order_book_builder().build()).await;
+
+        // bitstamp is only stale with its own timeout
+        let summary = get_merged_book(&exchanges_mutex, &StalenessTimeouts::default())
+            .await
+            .get_summary(&SummaryRequest::default());
+        assert!(summary.stale_exchanges.is_empty());
+
+        let staleness_timeouts =
+            StalenessTimeouts::default().with_exchange("bitstamp".into(), Duration::from_secs(1));
+        let summary = get_merged_book(&exchanges_mutex, &staleness_timeouts)
+            .await
+            .get_summary(&SummaryRequest::default());
+
+        assert_eq!(summary.stale_exchanges, vec!["bitstamp"]);
+        assert!(summary.bids.iter().all(|bid| bid.exchange == "binance"));
+        assert!(summary.asks.iter().all(|ask| ask.exchange == "binance"));
+    }
+}
@@ -0,0 +1,282 @@
+use crate::{
+    merged_book::{MergedBook, TakerFees},
+    order_book::{to_f64, PriceLevel, Side},
+    proto::{ArbitrageEvent, ArbitrageStatus},
+};
+use rust_decimal::Decimal;
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, Instant},
+};
+
+// buying on the ask exchange and selling on the bid exchange makes a profit after fees
+#[derive(Clone, Debug, PartialEq)]
+struct Crossing {
+    bid_exchange: &'static str,
+    ask_exchange: &'static str,
+    bid_price: Decimal,
+    ask_price: Decimal,
+    size: Decimal,
+    profit: Decimal,
+}
+
+struct OpenCrossing {
+    crossing: Crossing,
+    opened_at: Instant,
+}
+
+// follows the crossings of a pair from one merged book to the next
+pub struct ArbitrageTracker {
+    pair: String,
+    open: HashMap<(&'static str, &'static str), OpenCrossing>,
+}
+
+impl ArbitrageTracker {
+    pub fn new(pair: String) -> Self {
+        Self {
+            pair,
+            open: HashMap::new(),
+        }
+    }
+    // the opportunities opened, changed or closed by a new merged book
+    pub fn update(&mut self, merged_book: &MergedBook, now: Instant) -> Vec<ArbitrageEvent> {
+        let crossings = get_crossings(merged_book);
+        let sequence = merged_book.get_sequence();
+        let mut events = vec![];
+
+        let mut closed: Vec<(&'static str, &'static str)> = self
+            .open
+            .keys()
+            .filter(|key| {
+                !crossings
+                    .iter()
+                    .any(|crossing| (crossing.bid_exchange, crossing.ask_exchange) == **key)
+            })
+            .copied()
+            .collect();
+        closed.sort();
+        for key in closed {
+            if let Some(open) = self.open.remove(&key) {
+                events.push(self.to_event(
+                    ArbitrageStatus::Closed,
+                    &open.crossing,
+                    now.duration_since(open.opened_at),
+                    sequence,
+                ));
+            }
+        }
+
+        for crossing in crossings {
+            let key = (crossing.bid_exchange, crossing.ask_exchange);
+            match self.open.get_mut(&key) {
+                None => {
+                    events.push(self.to_event(
+                        ArbitrageStatus::Opened,
+                        &crossing,
+                        Duration::ZERO,
+                        sequence,
+                    ));
+                    self.open.insert(
+                        key,
+                        OpenCrossing {
+                            crossing,
+                            opened_at: now,
+                        },
+                    );
+                }
+                Some(open) if open.crossing != crossing => {
+                    open.crossing = crossing;
+                    let duration = now.duration_since(open.opened_at);
+                    let crossing = open.crossing.clone();
+                    events.push(self.to_event(
+                        ArbitrageStatus::Updated,
+                        &crossing,
+                        duration,
+                        sequence,
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+
+        events
+    }
+    fn to_event(
+        &self,
+        status: ArbitrageStatus,
+        crossing: &Crossing,
+        duration: Duration,
+        sequence: u64,
+    ) -> ArbitrageEvent {
+        ArbitrageEvent {
+            symbol: self.pair.clone(),
+            status: status.into(),
+            bid_exchange: crossing.bid_exchange.into(),
+            ask_exchange: crossing.ask_exchange.into(),
+            bid_price: to_f64(crossing.bid_price),
+            ask_price: to_f64(crossing.ask_price),
+            size: to_f64(crossing.size),
+            profit: to_f64(crossing.profit),
+            exact_bid_price: crossing.bid_price.to_string(),
+            exact_ask_price: crossing.ask_price.to_string(),
+            exact_size: crossing.size.to_string(),
+            exact_profit: crossing.profit.to_string(),
+            duration_ms: duration.as_millis() as u64,
+            sequence,
+        }
+    }
+}
+
+// every pair of exchanges where one bids more than the other asks once the fees are paid
+fn get_crossings(merged_book: &MergedBook) -> Vec<Crossing> {
+    // the merged levels keep the order of each exchange book
+    let by_exchange = |levels: &[PriceLevel]| {
+        let mut exchanges: BTreeMap<&'static str, Vec<PriceLevel>> = BTreeMap::new();
+        for level in levels {
+            exchanges.entry(level.exchange).or_default().push(*level);
+        }
+        exchanges
+    };
+    let bids = by_exchange(merged_book.get_levels(Side::Bid, false));
+    let asks = by_exchange(merged_book.get_levels(Side::Ask, false));
+
+    let mut crossings = vec![];
+    for (bid_exchange, bids) in &bids {
+        for (ask_exchange, asks) in &asks {
+            if bid_exchange == ask_exchange {
+                continue;
+            }
+            if let Some(crossing) = get_crossing(bids, asks, merged_book.get_taker_fees()) {
+                crossings.push(crossing);
+            }
+        }
+    }
+    crossings
+}
+
+// walks both books while selling the next bid still pays for buying the next ask
+fn get_crossing(
+    bids: &[PriceLevel],
+    asks: &[PriceLevel],
+    taker_fees: &TakerFees,
+) -> Option<Crossing> {
+    let (best_bid, best_ask) = (bids.first()?, asks.first()?);
+    let (mut bid_index, mut ask_index) = (0, 0);
+    let (mut bid_left, mut ask_left) = (best_bid.amount, best_ask.amount);
+    let (mut size, mut profit) = (Decimal::ZERO, Decimal::ZERO);
+
+    while let (Some(bid), Some(ask)) = (bids.get(bid_index), asks.get(ask_index)) {
+        let bid_price = taker_fees.get_adjusted_price(Side::Bid, bid);
+        let ask_price = taker_fees.get_adjusted_price(Side::Ask, ask);
+        if bid_price <= ask_price {
+            break;
+        }
+        let amount = bid_left.min(ask_left);
+        size += amount;
+        profit += amount * (bid_price - ask_price);
+        bid_left -= amount;
+        ask_left -= amount;
+        if bid_left.is_zero() {
+            bid_index += 1;
+            bid_left = bids.get(bid_index).map_or(Decimal::ZERO, |bid| bid.amount);
+        }
+        if ask_left.is_zero() {
+            ask_index += 1;
+            ask_left = asks.get(ask_index).map_or(Decimal::ZERO, |ask| ask.amount);
+        }
+    }
+
+    if size.is_zero() {
+        return None;
+    }
+    Some(Crossing {
+        bid_exchange: best_bid.exchange,
+        ask_exchange: best_ask.exchange,
+        bid_price: best_bid.price,
+        ask_price: best_ask.price,
+        size: size.normalize(),
+        profit: profit.normalize(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order_book::OrderBook;
+    use rust_decimal_macros::dec;
+
+    fn get_merged_book(kraken_ask: Decimal, taker_fees: &TakerFees) -> MergedBook {
+        let mut binance = OrderBook::new("binance");
+        binance.update(Side::Bid, dec!(101), dec!(1));
+        binance.update(Side::Bid, dec!(100.5), dec!(2));
+        binance.update(Side::Ask, dec!(102), dec!(1));
+        let mut kraken = OrderBook::new("kraken");
+        kraken.update(Side::Bid, dec!(99), dec!(1));
+        kraken.update(Side::Ask, kraken_ask, dec!(1.5));
+        kraken.update(Side::Ask, dec!(100.8), dec!(5));
+        MergedBook::new([&binance, &kraken].into_iter(), vec![])
+            .with_taker_fees(taker_fees)
+            .with_sequence(1, None)
+    }
+
+    #[test]
+    fn test_get_crossings() {
+        let crossings = get_crossings(&get_merged_book(dec!(100), &TakerFees::default()));
+        assert_eq!(
+            crossings,
+            vec![Crossing {
+                bid_exchange: "binance",
+                ask_exchange: "kraken",
+                bid_price: dec!(101),
+                ask_price: dec!(100),
+                size: dec!(1.5),
+                profit: dec!(1.25),
+            }]
+        );
+
+        // the fees eat part of the profit
+        let taker_fees = TakerFees::default().with_exchange("binance".into(), dec!(10));
+        let crossings = get_crossings(&get_merged_book(dec!(100), &taker_fees));
+        assert_eq!(crossings[0].size, dec!(1.5));
+        assert_eq!(crossings[0].profit, dec!(1.09875));
+
+        // and all of it when they are higher than the price gap
+        let taker_fees = TakerFees::default().with_exchange("binance".into(), dec!(100));
+        assert!(get_crossings(&get_merged_book(dec!(100), &taker_fees)).is_empty());
+    }
+
+    #[test]
+    fn test_arbitrage_tracker() {
+        let mut tracker = ArbitrageTracker::new("ethbtc".into());
+        let taker_fees = TakerFees::default();
+        let start = Instant::now();
+
+        let events = tracker.update(&get_merged_book(dec!(100), &taker_fees), start);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].status(), ArbitrageStatus::Opened);
+        assert_eq!(events[0].symbol, "ethbtc");
+        assert_eq!(events[0].exact_profit, "1.25");
+
+        // nothing changed
+        let now = start + Duration::from_millis(100);
+        assert!(tracker
+            .update(&get_merged_book(dec!(100), &taker_fees), now)
+            .is_empty());
+
+        let now = start + Duration::from_millis(200);
+        let events = tracker.update(&get_merged_book(dec!(100.2), &taker_fees), now);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].status(), ArbitrageStatus::Updated);
+        assert_eq!(events[0].exact_ask_price, "100.2");
+        assert_eq!(events[0].duration_ms, 200);
+
+        // higher fees close it, with the last values seen
+        let now = start + Duration::from_millis(500);
+        let taker_fees = TakerFees::default().with_exchange("binance".into(), dec!(100));
+        let events = tracker.update(&get_merged_book(dec!(100.2), &taker_fees), now);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].status(), ArbitrageStatus::Closed);
+        assert_eq!(events[0].exact_ask_price, "100.2");
+        assert_eq!(events[0].duration_ms, 500);
+    }
+}
@@ -1,11 +1,17 @@
 use crate::{
-    exchange::Exchange,
-    order_book::{OrderBook, OrderBookBuilder},
+    connection::{supervise, Backoff, Connection},
+    exchange::{Exchange, ExchangeSettings, Frame, SnapshotFrames},
+    merged_book::MAX_DEPTH,
+    order_book::{LevelBuilder, OrderBook, OrderBookBuilder},
+    recorder::FrameSource,
 };
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
 use futures_util::StreamExt;
 use serde::Deserialize;
-use tokio::sync::mpsc::Sender;
+use std::time::{Duration, UNIX_EPOCH};
 use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tracing::{debug, warn};
 
 #[cfg(not(test))]
 const BINANCE_WEB_SOCKET_URL: &str = "wss://stream.binance.com:9443/ws/";
@@ -13,76 +19,375 @@ const BINANCE_WEB_SOCKET_URL: &str = "wss://stream.binance.com:9443/ws/";
 #[cfg(test)]
 const BINANCE_WEB_SOCKET_URL: &str = "ws://localhost:8080/ws/";
 
+#[cfg(not(test))]
+const BINANCE_REST_URL: &str = "https://api.binance.com/api/v3/depth";
+
+#[cfg(test)]
+const BINANCE_REST_URL: &str = "http://localhost:8082/api/v3/depth";
+
+const BINANCE_SNAPSHOT_LIMIT: i32 = 1000;
+
+// snapshots taken for a gap before the session starts over
+const BINANCE_MAX_RESYNCS: u32 = 3;
+
+const BINANCE_DEPTH: usize = 10;
+
+// the depths of the partial book streams
+const BINANCE_DEPTHS: [usize; 3] = [5, 10, 20];
+
+const BINANCE_UPDATE_SPEED_MS: u64 = 100;
+
+const BINANCE_UPDATE_SPEEDS_MS: [u64; 2] = [100, 1000];
+
 #[derive(Deserialize)]
 pub struct Binance;
 
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum BinanceStream {
+    // @depth10 partial book snapshots
+    #[default]
+    Partial,
+    // @depth diff updates on top of a REST snapshot
+    Diff,
+}
+
 #[tonic::async_trait]
 impl Exchange for Binance {
     fn get_name() -> &'static str {
         "binance"
     }
-    async fn get_order_book(pair: String, sender: Sender<OrderBook>) -> () {
-        loop {
-            let subscription = BinanceSubscription::new(&pair, 10, 100);
-            let (ws_stream, _) = connect_async(subscription.to_url()).await.unwrap();
-            let (_, read) = ws_stream.split();
-
-            read.for_each(|message| async {
-                if let Ok(Message::Text(text)) = message {
-                    let response: Result<OrderBookBuilder<Binance>, _> =
-                        serde_json::from_str(&text);
-                    if let Ok(order_book) = response {
-                        if sender.send(order_book.build()).await.is_ok() {
-                            println!("Binance message sent")
-                        }
+    fn get_default_settings() -> ExchangeSettings {
+        ExchangeSettings::new(BINANCE_WEB_SOCKET_URL)
+            .with_rest_url(BINANCE_REST_URL)
+            .with_depth(BINANCE_DEPTH)
+            .with_update_speed_ms(BINANCE_UPDATE_SPEED_MS)
+    }
+    fn validate_settings(settings: &ExchangeSettings) -> Result<()> {
+        ExchangeSettings::check("depth", settings.depth, &BINANCE_DEPTHS)?;
+        ExchangeSettings::check(
+            "update_speed_ms",
+            settings.update_speed_ms,
+            &BINANCE_UPDATE_SPEEDS_MS,
+        )
+    }
+    async fn connect(
+        symbol: String,
+        settings: ExchangeSettings,
+        connection: Connection,
+    ) -> Result<()> {
+        let depth = settings.depth.unwrap_or(BINANCE_DEPTH);
+        let subscription = BinanceSubscription::new(&settings, &symbol, Some(depth));
+        let (ws_stream, _) = connect_async(subscription.to_url()).await?;
+        let (_, mut read) = ws_stream.split();
+
+        while let Some(message) = read.next().await {
+            if let Message::Text(text) = message? {
+                connection.record_message(&text);
+                match Binance::parse_frame(&text) {
+                    Ok(Frame::Book(order_book)) => {
+                        connection.send(order_book).await?;
+                        debug!("order book sent")
                     }
+                    Ok(_) => {}
+                    Err(_) => connection.record_parse_failure(),
                 }
-            })
-            .await;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// the frames of the partial book stream, the diff stream needs a snapshot
+impl SnapshotFrames for Binance {
+    fn parse_frame(frame: &str) -> Result<Frame> {
+        let order_book: OrderBookBuilder<Binance> = serde_json::from_str(frame)?;
+        Ok(Frame::Book(order_book.build()))
+    }
+}
+
+impl Binance {
+    pub async fn get_diff_order_book(
+        symbol: String,
+        settings: ExchangeSettings,
+        connection: Connection,
+    ) {
+        supervise(connection, Backoff::default(), |connection| {
+            Binance::connect_diff_depth(symbol.clone(), settings.clone(), connection)
+        })
+        .await
+    }
+
+    // https://binance-docs.github.io/apidocs/spot/en/#how-to-manage-a-local-order-book-correctly
+    async fn connect_diff_depth(
+        symbol: String,
+        settings: ExchangeSettings,
+        connection: Connection,
+    ) -> Result<()> {
+        let rest_url = settings.rest_url.as_deref().unwrap_or(BINANCE_REST_URL);
+        let subscription = BinanceSubscription::new(&settings, &symbol, None);
+        let (ws_stream, _) = connect_async(subscription.to_url()).await?;
+        let (_, mut read) = ws_stream.split();
+
+        // the stream buffers the updates received while we wait for the snapshot
+        let mut book = BinanceDiffBook::new(get_snapshot(rest_url, &symbol, &connection).await?);
+        connection.send(book.order_book.get_top(MAX_DEPTH)).await?;
+
+        while let Some(message) = read.next().await {
+            if let Message::Text(text) = message? {
+                connection.record_message(&text);
+                let update: BinanceDepthUpdate = match serde_json::from_str(&text) {
+                    Ok(update) => update,
+                    Err(_) => {
+                        connection.record_parse_failure();
+                        continue;
+                    }
+                };
+
+                match book.apply(&update) {
+                    DiffOutcome::Ignored => continue,
+                    DiffOutcome::Applied => {}
+                    DiffOutcome::Gap => {
+                        book = resync(rest_url, &symbol, &update, &connection).await?
+                    }
+                }
+
+                connection.send(book.order_book.get_top(MAX_DEPTH)).await?;
+                debug!("order book sent")
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// the snapshot can lag behind the stream, a new one is taken until it reaches the update
+async fn resync(
+    rest_url: &str,
+    symbol: &str,
+    update: &BinanceDepthUpdate,
+    connection: &Connection,
+) -> Result<BinanceDiffBook> {
+    for attempt in 1..=BINANCE_MAX_RESYNCS {
+        warn!(attempt, "diff depth gap, resyncing from snapshot");
+        let mut book = BinanceDiffBook::new(get_snapshot(rest_url, symbol, connection).await?);
+        if book.apply(update) != DiffOutcome::Gap {
+            return Ok(book);
         }
     }
+    Err(anyhow!(
+        "diff depth gap still there after {} snapshots",
+        BINANCE_MAX_RESYNCS
+    ))
+}
+
+async fn get_snapshot(
+    rest_url: &str,
+    symbol: &str,
+    connection: &Connection,
+) -> Result<BinanceSnapshot> {
+    let url = format!(
+        "{}?symbol={}&limit={}",
+        rest_url,
+        symbol.to_uppercase(),
+        BINANCE_SNAPSHOT_LIMIT
+    );
+    let body = reqwest::get(url).await?.error_for_status()?.text().await?;
+    // a capture of the diff stream can't rebuild the book without it
+    connection.record_snapshot(&body);
+    Ok(serde_json::from_str(&body)?)
 }
 
 struct BinanceSubscription<'a> {
-    pair: &'a String,
-    depth: i32,
-    update_speed: i32,
+    url: &'a str,
+    symbol: &'a str,
+    depth: Option<usize>,
+    update_speed: u64,
 }
 
 impl<'a> BinanceSubscription<'a> {
-    fn new(pair: &'a String, depth: i32, update_speed: i32) -> Self {
+    fn new(settings: &'a ExchangeSettings, symbol: &'a str, depth: Option<usize>) -> Self {
         Self {
-            pair,
+            url: &settings.websocket_url,
+            symbol,
             depth,
-            update_speed,
+            update_speed: settings.update_speed_ms.unwrap_or(BINANCE_UPDATE_SPEED_MS),
         }
     }
     fn to_url(&self) -> String {
-        format!(
-            "{}{}@depth{}@{}ms",
-            BINANCE_WEB_SOCKET_URL, self.pair, self.depth, self.update_speed
-        )
+        match self.depth {
+            Some(depth) => format!(
+                "{}{}@depth{}@{}ms",
+                self.url,
+                self.symbol.to_lowercase(),
+                depth,
+                self.update_speed
+            ),
+            None => format!(
+                "{}{}@depth@{}ms",
+                self.url,
+                self.symbol.to_lowercase(),
+                self.update_speed
+            ),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BinanceSnapshot {
+    last_update_id: u64,
+    bids: Vec<LevelBuilder<Binance>>,
+    asks: Vec<LevelBuilder<Binance>>,
+}
+
+#[derive(Deserialize)]
+struct BinanceDepthUpdate {
+    // milliseconds since the unix epoch
+    #[serde(rename = "E")]
+    event_time: u64,
+    #[serde(rename = "U")]
+    first_update_id: u64,
+    #[serde(rename = "u")]
+    final_update_id: u64,
+    #[serde(rename = "b")]
+    bids: Vec<LevelBuilder<Binance>>,
+    #[serde(rename = "a")]
+    asks: Vec<LevelBuilder<Binance>>,
+}
+
+#[derive(Debug, PartialEq)]
+enum DiffOutcome {
+    Ignored,
+    Applied,
+    Gap,
+}
+
+struct BinanceDiffBook {
+    last_update_id: u64,
+    // whether an update has been applied on top of the snapshot
+    synced: bool,
+    order_book: OrderBook,
+}
+
+impl BinanceDiffBook {
+    fn new(snapshot: BinanceSnapshot) -> Self {
+        Self {
+            last_update_id: snapshot.last_update_id,
+            synced: false,
+            order_book: OrderBookBuilder {
+                bids: snapshot.bids,
+                asks: snapshot.asks,
+            }
+            .build(),
+        }
+    }
+    fn apply(&mut self, update: &BinanceDepthUpdate) -> DiffOutcome {
+        if update.final_update_id <= self.last_update_id {
+            return DiffOutcome::Ignored;
+        }
+
+        // the first update must straddle the snapshot, the next ones must follow each other
+        let next_update_id = self.last_update_id + 1;
+        let is_gap = if self.synced {
+            update.first_update_id != next_update_id
+        } else {
+            update.first_update_id > next_update_id
+        };
+        if is_gap {
+            return DiffOutcome::Gap;
+        }
+
+        self.order_book.apply(&update.bids, &update.asks);
+        self.order_book
+            .set_event_time(UNIX_EPOCH + Duration::from_millis(update.event_time));
+        self.last_update_id = update.final_update_id;
+        self.synced = true;
+        DiffOutcome::Applied
+    }
+}
+
+// replays a capture of either stream, the diff updates are applied on the recorded rest snapshots
+// like the live session does
+#[derive(Default)]
+pub struct BinanceCaptureFrames {
+    book: Option<BinanceDiffBook>,
+    // the update that found a gap, applied on the next snapshot
+    gap: Option<BinanceDepthUpdate>,
+}
+
+impl BinanceCaptureFrames {
+    pub fn parse_frame(&mut self, source: FrameSource, frame: &str) -> Result<Frame> {
+        if source == FrameSource::Rest {
+            let mut book = BinanceDiffBook::new(serde_json::from_str(frame)?);
+            if let Some(update) = &self.gap {
+                if book.apply(update) == DiffOutcome::Gap {
+                    // the session took another snapshot
+                    self.book = None;
+                    return Ok(Frame::Ignored);
+                }
+            }
+            self.gap = None;
+            let order_book = book.order_book.get_top(MAX_DEPTH);
+            self.book = Some(book);
+            return Ok(Frame::Book(order_book));
+        }
+
+        // the partial book frames have none of the update ids
+        let update: BinanceDepthUpdate = match serde_json::from_str(frame) {
+            Ok(update) => update,
+            Err(_) => return Binance::parse_frame(frame),
+        };
+        let book = match &mut self.book {
+            Some(book) => book,
+            // waiting for a snapshot
+            None => return Ok(Frame::Ignored),
+        };
+        match book.apply(&update) {
+            DiffOutcome::Ignored => Ok(Frame::Ignored),
+            DiffOutcome::Applied => Ok(Frame::Book(book.order_book.get_top(MAX_DEPTH))),
+            DiffOutcome::Gap => {
+                self.book = None;
+                self.gap = Some(update);
+                Ok(Frame::Ignored)
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{test_data::get_binance_websocket_response, test_server::TestServer};
+    use crate::{
+        exchange::get_order_book,
+        recorder::{get_capture_files, read_captures, Recorder, RecorderSettings},
+        test_data::{
+            get_binance_diff_depth_gap_update, get_binance_diff_depth_updates,
+            get_binance_resync_snapshot_response, get_binance_snapshot_response,
+            get_binance_stale_resync_snapshot_response, get_binance_websocket_response,
+        },
+        test_server::{TestRestServer, TestServer},
+    };
+    use std::{env::temp_dir, fs};
     use tokio::spawn;
 
     #[tokio::test]
     async fn test_binance_websocket() {
         let mut server = TestServer::new("8080").await;
-        let (sender, mut receiver) = server.get_channels();
+        let (connection, mut receiver) = server.get_connection::<Binance>();
 
-        spawn(Binance::get_order_book("ethbtc".into(), sender));
+        spawn(get_order_book::<Binance>(
+            "ETHBTC".into(),
+            Binance::get_default_settings(),
+            connection,
+        ));
 
         server.send_message(get_binance_websocket_response()).await;
 
         if let Some(order_book) = receiver.recv().await {
             assert_eq!(order_book.get_exchange_name(), "binance");
-            let (bids, asks) = order_book.get_levels();
+            let (bids, asks) = order_book.get_levels(10);
 
             assert_eq!(bids.len(), 10);
             assert_eq!(asks.len(), 10);
@@ -96,4 +401,138 @@ mod tests {
             assert_eq!(asks[9].price, 0.067966);
         }
     }
+
+    #[test]
+    fn test_binance_subscription_url() {
+        let settings = Binance::get_default_settings();
+        assert_eq!(
+            BinanceSubscription::new(&settings, "ETHBTC", Some(10)).to_url(),
+            "ws://localhost:8080/ws/ethbtc@depth10@100ms"
+        );
+        assert_eq!(
+            BinanceSubscription::new(&settings, "ETHBTC", None).to_url(),
+            "ws://localhost:8080/ws/ethbtc@depth@100ms"
+        );
+        let settings = settings.with_update_speed_ms(1000);
+        assert_eq!(
+            BinanceSubscription::new(&settings, "ETHBTC", Some(5)).to_url(),
+            "ws://localhost:8080/ws/ethbtc@depth5@1000ms"
+        );
+        assert!(Binance::validate_settings(&settings).is_ok());
+        assert!(Binance::validate_settings(&settings.with_depth(15)).is_err());
+    }
+
+    #[test]
+    fn test_binance_diff_book() {
+        let snapshot: BinanceSnapshot =
+            serde_json::from_str(get_binance_snapshot_response()).unwrap();
+        let mut book = BinanceDiffBook::new(snapshot);
+        let updates: Vec<BinanceDepthUpdate> = get_binance_diff_depth_updates()
+            .iter()
+            .map(|update| serde_json::from_str(update).unwrap())
+            .collect();
+
+        // already contained in the snapshot
+        assert_eq!(book.apply(&updates[0]), DiffOutcome::Ignored);
+        // straddles the snapshot
+        assert_eq!(book.apply(&updates[1]), DiffOutcome::Applied);
+        // follows the previous update
+        assert_eq!(book.apply(&updates[2]), DiffOutcome::Applied);
+        assert_eq!(book.last_update_id, 104);
+        assert_eq!(
+            book.order_book.get_event_time(),
+            Some(UNIX_EPOCH + Duration::from_millis(1681990000200))
+        );
+
+        let gap: BinanceDepthUpdate =
+            serde_json::from_str(get_binance_diff_depth_gap_update()).unwrap();
+        assert_eq!(book.apply(&gap), DiffOutcome::Gap);
+        assert_eq!(book.last_update_id, 104);
+    }
+
+    #[tokio::test]
+    async fn test_binance_diff_depth_websocket() {
+        let mut server = TestServer::new("8080").await;
+        let mut rest_server = TestRestServer::new("8082").await;
+        let (connection, mut receiver) = server.get_connection::<Binance>();
+        let directory = temp_dir().join(format!("binance-diff-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let recorder = Recorder::new(RecorderSettings::new(directory.clone())).unwrap();
+        let connection = connection.with_recorder(Some(recorder.clone()));
+
+        rest_server
+            .send_response(get_binance_snapshot_response())
+            .await;
+
+        spawn(Binance::get_diff_order_book(
+            "ethbtc".into(),
+            Binance::get_default_settings(),
+            connection,
+        ));
+
+        // the snapshot is published first
+        let order_book = receiver.recv().await.unwrap();
+        let (bids, asks) = order_book.get_levels(10);
+        assert_eq!(bids.len(), 3);
+        assert_eq!(asks.len(), 3);
+        assert_eq!(bids[0].price, 0.067955);
+
+        for update in get_binance_diff_depth_updates() {
+            server.send_message(update).await;
+        }
+
+        // the first update is ignored, the second removes the best bid
+        let (bids, _) = receiver.recv().await.unwrap().get_levels(10);
+        assert_eq!(bids.len(), 2);
+        assert_eq!(bids[0].price, 0.067954);
+
+        // the third update adds a new best ask
+        let (_, asks) = receiver.recv().await.unwrap().get_levels(10);
+        assert_eq!(asks.len(), 4);
+        assert_eq!(asks[0].price, 0.0679555);
+
+        // a gap triggers new snapshots until one reaches the update, which is applied on top of it
+        rest_server
+            .send_response(get_binance_stale_resync_snapshot_response())
+            .await;
+        rest_server
+            .send_response(get_binance_resync_snapshot_response())
+            .await;
+        server
+            .send_message(get_binance_diff_depth_gap_update())
+            .await;
+
+        let (bids, asks) = receiver.recv().await.unwrap().get_levels(10);
+        assert_eq!(bids.len(), 1);
+        assert_eq!(bids[0].price, 0.06796);
+        assert_eq!(bids[0].amount, 5.0);
+        assert_eq!(asks.len(), 1);
+        assert_eq!(asks[0].price, 0.068);
+
+        // the snapshots are captured with the updates, in the order they were received
+        recorder.stop().await;
+        let frames: Vec<(FrameSource, String)> = get_capture_files(&directory)
+            .unwrap()
+            .iter()
+            .flat_map(|file| read_captures(file).unwrap())
+            .map(|frame| frame.map(|frame| (frame.source, frame.frame)))
+            .collect::<Result<_>>()
+            .unwrap();
+        let [first, second, third] = get_binance_diff_depth_updates();
+        let expected = [
+            (FrameSource::Rest, get_binance_snapshot_response()),
+            (FrameSource::Websocket, first),
+            (FrameSource::Websocket, second),
+            (FrameSource::Websocket, third),
+            (FrameSource::Websocket, get_binance_diff_depth_gap_update()),
+            (
+                FrameSource::Rest,
+                get_binance_stale_resync_snapshot_response(),
+            ),
+            (FrameSource::Rest, get_binance_resync_snapshot_response()),
+        ]
+        .map(|(source, frame)| (source, frame.to_string()));
+        assert_eq!(frames, expected);
+        fs::remove_dir_all(directory).unwrap();
+    }
 }
@@ -1,11 +1,14 @@
 use crate::{
-    exchange::Exchange,
-    order_book::{OrderBook, OrderBookBuilder},
+    connection::Connection,
+    exchange::{Exchange, ExchangeSettings, Frame, SnapshotFrames},
+    order_book::OrderBookBuilder,
 };
+use anyhow::Result;
 use futures_util::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
-use tokio::sync::mpsc::Sender;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tracing::debug;
 
 #[cfg(not(test))]
 const BITSTAMP_WEB_SOCKET_URL: &str = "wss://ws.bitstamp.net/";
@@ -21,36 +24,59 @@ impl Exchange for Bitstamp {
     fn get_name() -> &'static str {
         "bitstamp"
     }
-    async fn get_order_book(pair: String, sender: Sender<OrderBook>) -> () {
-        loop {
-            let (ws_stream, _) = connect_async(BITSTAMP_WEB_SOCKET_URL).await.unwrap();
-            let (mut write, read) = ws_stream.split();
-
-            write
-                .send(Message::Text(
-                    BitstampSubscription::new("bts:subscribe", format!("order_book_{}", pair))
-                        .to_json(),
-                ))
-                .await
-                .unwrap();
-
-            read.for_each(|message| async {
-                if let Ok(Message::Text(text)) = message {
-                    let response_event: BitstampResponseEvent =
-                        serde_json::from_str(&text).unwrap();
-                    if let BitstampWebSocketEvent::Data = response_event.event {
-                        let bitstamp_response: Result<BitstampResponse, _> =
-                            serde_json::from_str(&text);
-                        if let Ok(bitstamp_response) = bitstamp_response {
-                            let order_book: OrderBookBuilder<Bitstamp> = bitstamp_response.into();
-                            if sender.send(order_book.build()).await.is_ok() {
-                                println!("Bitstamp message sent")
-                            }
-                        }
+    fn get_default_settings() -> ExchangeSettings {
+        ExchangeSettings::new(BITSTAMP_WEB_SOCKET_URL)
+    }
+    async fn connect(
+        symbol: String,
+        settings: ExchangeSettings,
+        connection: Connection,
+    ) -> Result<()> {
+        let (ws_stream, _) = connect_async(&settings.websocket_url).await?;
+        let (mut write, mut read) = ws_stream.split();
+
+        write
+            .send(Message::Text(
+                BitstampSubscription::new("bts:subscribe", format!("order_book_{}", symbol))
+                    .to_json(),
+            ))
+            .await?;
+
+        while let Some(message) = read.next().await {
+            if let Message::Text(text) = message? {
+                connection.record_message(&text);
+                match Bitstamp::parse_frame(&text) {
+                    Ok(Frame::Book(order_book)) => {
+                        connection.send(order_book).await?;
+                        debug!("order book sent")
                     }
+                    Ok(Frame::Reconnect) => return Ok(()),
+                    Ok(Frame::Ignored) => {}
+                    Err(_) => connection.record_parse_failure(),
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl SnapshotFrames for Bitstamp {
+    fn parse_frame(frame: &str) -> Result<Frame> {
+        let response_event: BitstampResponseEvent = serde_json::from_str(frame)?;
+        match response_event.event {
+            BitstampWebSocketEvent::Data => {
+                let bitstamp_response: BitstampResponse = serde_json::from_str(frame)?;
+                let event_time = bitstamp_response.get_event_time();
+                let order_book: OrderBookBuilder<Bitstamp> = bitstamp_response.into();
+                let mut order_book = order_book.build();
+                if let Some(event_time) = event_time {
+                    order_book.set_event_time(event_time);
                 }
-            })
-            .await;
+                Ok(Frame::Book(order_book))
+            }
+            BitstampWebSocketEvent::BtsRequestReconnect => Ok(Frame::Reconnect),
+            _ => Ok(Frame::Ignored),
         }
     }
 }
@@ -79,14 +105,29 @@ impl BitstampSubscription {
     }
 }
 
+#[derive(Deserialize)]
+struct BitstampData {
+    #[serde(flatten)]
+    order_book: OrderBookBuilder<Bitstamp>,
+    // microseconds since the unix epoch
+    microtimestamp: Option<String>,
+}
+
 #[derive(Deserialize)]
 struct BitstampResponse {
-    data: OrderBookBuilder<Bitstamp>,
+    data: BitstampData,
+}
+
+impl BitstampResponse {
+    fn get_event_time(&self) -> Option<SystemTime> {
+        let microtimestamp = self.data.microtimestamp.as_ref()?.parse().ok()?;
+        Some(UNIX_EPOCH + Duration::from_micros(microtimestamp))
+    }
 }
 
 impl From<BitstampResponse> for OrderBookBuilder<Bitstamp> {
     fn from(bitstamp_response: BitstampResponse) -> Self {
-        bitstamp_response.data
+        bitstamp_response.data.order_book
     }
 }
 
@@ -96,6 +137,10 @@ enum BitstampWebSocketEvent {
     BtsSubscriptionSucceded,
     #[serde(rename = "data")]
     Data,
+    #[serde(rename = "bts:request_reconnect")]
+    BtsRequestReconnect,
+    #[serde(other)]
+    Other,
 }
 #[derive(Deserialize)]
 struct BitstampResponseEvent {
@@ -105,21 +150,32 @@ struct BitstampResponseEvent {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{test_data::get_bitstamp_websocket_response, test_server::TestServer};
+    use crate::{
+        exchange::get_order_book, test_data::get_bitstamp_websocket_response,
+        test_server::TestServer,
+    };
     use tokio::spawn;
 
     #[tokio::test]
     async fn test_bitstamp_websocket() {
         let mut server = TestServer::new("8081").await;
-        let (sender, mut receiver) = server.get_channels();
+        let (connection, mut receiver) = server.get_connection::<Bitstamp>();
 
-        spawn(Bitstamp::get_order_book("ethbtc".into(), sender));
+        spawn(get_order_book::<Bitstamp>(
+            "ethbtc".into(),
+            Bitstamp::get_default_settings(),
+            connection,
+        ));
 
         server.send_message(get_bitstamp_websocket_response()).await;
 
         if let Some(order_book) = receiver.recv().await {
             assert_eq!(order_book.get_exchange_name(), "bitstamp");
-            let (bids, asks) = order_book.get_levels();
+            assert_eq!(
+                order_book.get_event_time(),
+                Some(UNIX_EPOCH + Duration::from_micros(1682167286795358))
+            );
+            let (bids, asks) = order_book.get_levels(10);
 
             assert_eq!(bids.len(), 10);
             assert_eq!(asks.len(), 10);
@@ -1,12 +1,113 @@
 mod proto;
-use crate::proto::{Empty, OrderbookAggregatorClient};
+use crate::proto::{
+    ArbitrageRequest, FillRequest, OrderSide, OrderbookAggregatorClient, SummaryRequest,
+};
 use anyhow::Result;
+use clap::{Parser, ValueEnum};
+
+#[derive(Clone, ValueEnum)]
+enum FillSide {
+    Buy,
+    Sell,
+}
+
+#[derive(Parser)]
+struct Args {
+    /// Pair to subscribe to, can be left out when the server aggregates a single pair
+    #[arg(short, long, default_value = "")]
+    symbol: String,
+    /// Levels per side, 10 when unset
+    #[arg(long, default_value_t = 0)]
+    depth: u32,
+    /// Exchanges to merge, all of them when unset
+    #[arg(long, value_delimiter = ',')]
+    exchanges: Vec<String>,
+    /// Minimum milliseconds between two summaries
+    #[arg(long, default_value_t = 0)]
+    min_interval_ms: u64,
+    /// One level per price with the amount of each exchange
+    #[arg(long)]
+    consolidated: bool,
+    /// Merge on the prices once the taker fees are paid
+    #[arg(long)]
+    fee_adjusted: bool,
+    /// Basis points from the mid within which the depth is summed
+    #[arg(long, value_delimiter = ',')]
+    depth_bands_bps: Vec<f64>,
+    /// Quote currency amounts to compute the average fill prices for
+    #[arg(long, value_delimiter = ',')]
+    vwap_notionals: Vec<f64>,
+    /// Print the level changes instead of full summaries
+    #[arg(long)]
+    deltas: bool,
+    /// Print the arbitrage opportunities between the exchanges
+    #[arg(long)]
+    arbitrage: bool,
+    /// Estimate the fill of a market order instead of streaming summaries
+    #[arg(long, value_enum)]
+    simulate: Option<FillSide>,
+    /// Size of the simulated order in the base currency
+    #[arg(long, default_value_t = 0.0)]
+    quantity: f64,
+    /// Size of the simulated order in the quote currency
+    #[arg(long, default_value_t = 0.0)]
+    notional: f64,
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let args = Args::parse();
     let mut client = OrderbookAggregatorClient::connect("http://[::1]:10000").await?;
 
-    let mut stream = client.book_summary(Empty {}).await?.into_inner();
+    if let Some(side) = args.simulate {
+        let side = match side {
+            FillSide::Buy => OrderSide::Buy,
+            FillSide::Sell => OrderSide::Sell,
+        };
+        let request = FillRequest {
+            symbol: args.symbol,
+            side: side.into(),
+            quantity: args.quantity,
+            notional: args.notional,
+            exchanges: args.exchanges,
+            fee_adjusted: args.fee_adjusted,
+        };
+        let estimate = client.simulate_fill(request).await?.into_inner();
+        println!("fill = {:?}", estimate);
+        return Ok(());
+    }
+
+    if args.arbitrage {
+        let request = ArbitrageRequest {
+            symbol: args.symbol,
+        };
+        let mut stream = client.arbitrage_opportunities(request).await?.into_inner();
+        while let Some(event) = stream.message().await? {
+            println!("arbitrage = {:?}", event);
+        }
+        return Ok(());
+    }
+
+    let request = SummaryRequest {
+        depth: args.depth,
+        exchanges: args.exchanges,
+        min_interval_ms: args.min_interval_ms,
+        symbol: args.symbol,
+        consolidated: args.consolidated,
+        fee_adjusted: args.fee_adjusted,
+        depth_bands_bps: args.depth_bands_bps,
+        vwap_notionals: args.vwap_notionals,
+    };
+
+    if args.deltas {
+        let mut stream = client.book_deltas(request).await?.into_inner();
+        while let Some(delta) = stream.message().await? {
+            println!("delta = {:?}", delta);
+        }
+        return Ok(());
+    }
+
+    let mut stream = client.book_summary(request).await?.into_inner();
 
     while let Some(summary) = stream.message().await? {
         println!("summary = {:?}", summary);
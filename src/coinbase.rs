@@ -0,0 +1,213 @@
+use crate::{
+    connection::Connection,
+    exchange::{Exchange, ExchangeSettings},
+    merged_book::MAX_DEPTH,
+    order_book::{LevelBuilder, OrderBook, Side},
+};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use futures_util::{SinkExt, StreamExt};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tracing::debug;
+
+#[cfg(not(test))]
+const COINBASE_WEB_SOCKET_URL: &str = "wss://ws-feed.exchange.coinbase.com";
+
+#[cfg(test)]
+const COINBASE_WEB_SOCKET_URL: &str = "ws://localhost:8083/ws/";
+
+#[derive(Deserialize)]
+pub struct Coinbase;
+
+#[tonic::async_trait]
+impl Exchange for Coinbase {
+    fn get_name() -> &'static str {
+        "coinbase"
+    }
+    fn get_default_settings() -> ExchangeSettings {
+        ExchangeSettings::new(COINBASE_WEB_SOCKET_URL)
+    }
+    // https://docs.cloud.coinbase.com/exchange/docs/websocket-channels#level2-channel
+    async fn connect(
+        product_id: String,
+        settings: ExchangeSettings,
+        connection: Connection,
+    ) -> Result<()> {
+        let (ws_stream, _) = connect_async(&settings.websocket_url).await?;
+        let (mut write, mut read) = ws_stream.split();
+
+        write
+            .send(Message::Text(
+                CoinbaseSubscription::new(&product_id, "level2").to_json(),
+            ))
+            .await?;
+
+        // updates are only meaningful once the snapshot has been received
+        let mut order_book: Option<OrderBook> = None;
+
+        while let Some(message) = read.next().await {
+            if let Message::Text(text) = message? {
+                connection.record_message(&text);
+                let response: CoinbaseResponse = match serde_json::from_str(&text) {
+                    Ok(response) => response,
+                    Err(_) => {
+                        connection.record_parse_failure();
+                        continue;
+                    }
+                };
+
+                match (response, order_book.as_mut()) {
+                    (CoinbaseResponse::Snapshot { bids, asks, time }, _) => {
+                        let mut snapshot = OrderBook::new(Coinbase::get_name());
+                        snapshot.apply(&bids, &asks);
+                        if let Some(time) = time {
+                            snapshot.set_event_time(time.into());
+                        }
+                        order_book = Some(snapshot);
+                    }
+                    (CoinbaseResponse::L2update { changes, time }, Some(order_book)) => {
+                        for CoinbaseChange(side, price, amount) in changes {
+                            order_book.update(side.into(), price, amount);
+                        }
+                        order_book.set_event_time(time.into());
+                    }
+                    (CoinbaseResponse::Error { message }, _) => return Err(anyhow!(message)),
+                    _ => continue,
+                }
+
+                if let Some(order_book) = &order_book {
+                    connection.send(order_book.get_top(MAX_DEPTH)).await?;
+                    debug!("order book sent")
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct CoinbaseSubscription<'a> {
+    #[serde(rename = "type")]
+    message_type: &'a str,
+    product_ids: Vec<&'a str>,
+    channels: Vec<&'a str>,
+}
+
+impl<'a> CoinbaseSubscription<'a> {
+    fn new(product_id: &'a str, channel: &'a str) -> Self {
+        Self {
+            message_type: "subscribe",
+            product_ids: vec![product_id],
+            channels: vec![channel],
+        }
+    }
+    fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum CoinbaseResponse {
+    Snapshot {
+        bids: Vec<LevelBuilder<Coinbase>>,
+        asks: Vec<LevelBuilder<Coinbase>>,
+        // older snapshots don't have it
+        #[serde(default)]
+        time: Option<DateTime<Utc>>,
+    },
+    L2update {
+        changes: Vec<CoinbaseChange>,
+        time: DateTime<Utc>,
+    },
+    Error {
+        message: String,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum CoinbaseSide {
+    Buy,
+    Sell,
+}
+
+impl From<CoinbaseSide> for Side {
+    fn from(side: CoinbaseSide) -> Self {
+        match side {
+            CoinbaseSide::Buy => Side::Bid,
+            CoinbaseSide::Sell => Side::Ask,
+        }
+    }
+}
+
+// ["buy", "0.06795", "1.2"], a zero size removes the level
+#[derive(Deserialize)]
+struct CoinbaseChange(CoinbaseSide, Decimal, Decimal);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        exchange::get_order_book,
+        test_data::{get_coinbase_l2update_response, get_coinbase_snapshot_response},
+        test_server::TestServer,
+    };
+    use tokio::spawn;
+
+    #[tokio::test]
+    async fn test_coinbase_websocket() {
+        let mut server = TestServer::new("8083").await;
+        let (connection, mut receiver) = server.get_connection::<Coinbase>();
+
+        spawn(get_order_book::<Coinbase>(
+            "ETH-BTC".into(),
+            Coinbase::get_default_settings(),
+            connection,
+        ));
+
+        // updates received before the snapshot are dropped
+        server.send_message(get_coinbase_l2update_response()).await;
+        server.send_message(get_coinbase_snapshot_response()).await;
+
+        let order_book = receiver.recv().await.unwrap();
+        assert_eq!(order_book.get_exchange_name(), "coinbase");
+        let (bids, asks) = order_book.get_levels(10);
+
+        assert_eq!(bids.len(), 3);
+        assert_eq!(asks.len(), 3);
+
+        // highest price first for bids
+        assert_eq!(bids[0].price, 0.06791);
+        assert_eq!(bids[2].price, 0.06789);
+
+        // lowest price first for asks
+        assert_eq!(asks[0].price, 0.06792);
+        assert_eq!(asks[2].price, 0.06794);
+
+        server.send_message(get_coinbase_l2update_response()).await;
+
+        let order_book = receiver.recv().await.unwrap();
+        let (bids, asks) = order_book.get_levels(10);
+
+        // the best bid is removed and the best ask is updated
+        assert_eq!(bids.len(), 2);
+        assert_eq!(bids[0].price, 0.0679);
+        assert_eq!(asks[0].price, 0.06792);
+        assert_eq!(asks[0].amount, 2.5);
+        assert_eq!(
+            order_book.get_event_time(),
+            Some(
+                "2023-04-22T12:41:26.795358Z"
+                    .parse::<DateTime<Utc>>()
+                    .unwrap()
+                    .into()
+            )
+        );
+    }
+}
@@ -0,0 +1,489 @@
+use crate::{
+    aggregator::{StalenessTimeouts, CHANNEL_BUFFER_SIZE},
+    binance::{Binance, BinanceStream},
+    bitstamp::Bitstamp,
+    coinbase::Coinbase,
+    exchange::{Exchange, ExchangeSettings},
+    export::{ExportFormat, ExportSettings},
+    kraken::Kraken,
+    logging::{self, LogFormat, DEFAULT_LOG_LEVEL},
+    merged_book::{TakerFees, MAX_DEPTH},
+    recorder::RecorderSettings,
+};
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+// environment variables starting with it override the config file, like ORDERBOOK_PAIRS
+const ENV_PREFIX: &str = "ORDERBOOK_";
+
+const DEFAULT_LISTEN_ADDRESS: &str = "[::1]:10000";
+const DEFAULT_STALE_AFTER_MS: u64 = 10000;
+
+// settings of the server, every one of them is optional
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub listen_address: SocketAddr,
+    // serves the prometheus metrics on /metrics when set
+    pub metrics_address: Option<SocketAddr>,
+    pub log_format: LogFormat,
+    // like info, or a filter like warn,server=debug
+    pub log_level: String,
+    pub pairs: Vec<String>,
+    // like etheur=ethbtc,btceur
+    pub synthetic: Vec<String>,
+    // the bundled instruments when unset
+    pub instruments: Option<PathBuf>,
+    pub binance_stream: BinanceStream,
+    pub stale_after_ms: u64,
+    pub channel_buffer_size: usize,
+    pub exchanges: BTreeMap<String, ExchangeConfig>,
+    // captures the raw binance and bitstamp frames when set
+    pub recorder: Option<RecorderSettings>,
+    // writes the summaries of every pair to csv and parquet files when set
+    pub export: Option<ExportSettings>,
+}
+
+// the exchange defaults are used for the unset settings
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExchangeConfig {
+    pub enabled: Option<bool>,
+    pub websocket_url: Option<String>,
+    pub rest_url: Option<String>,
+    pub depth: Option<usize>,
+    pub update_speed_ms: Option<u64>,
+    pub taker_fee_bps: Option<Decimal>,
+    pub stale_after_ms: Option<u64>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            listen_address: DEFAULT_LISTEN_ADDRESS.parse().unwrap(),
+            metrics_address: None,
+            log_format: LogFormat::default(),
+            log_level: DEFAULT_LOG_LEVEL.into(),
+            pairs: vec![],
+            synthetic: vec![],
+            instruments: None,
+            binance_stream: BinanceStream::default(),
+            stale_after_ms: DEFAULT_STALE_AFTER_MS,
+            channel_buffer_size: CHANNEL_BUFFER_SIZE,
+            exchanges: BTreeMap::new(),
+            recorder: None,
+            export: None,
+        }
+    }
+}
+
+impl Config {
+    // the file when given, then the ORDERBOOK_ environment variables
+    pub fn load(
+        path: Option<&Path>,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self> {
+        let mut config = match path {
+            Some(path) => {
+                let toml = fs::read_to_string(path)
+                    .with_context(|| format!("can't read the config from {}", path.display()))?;
+                Self::parse(&toml)
+                    .with_context(|| format!("invalid config in {}", path.display()))?
+            }
+            None => Self::default(),
+        };
+        for (name, value) in vars {
+            if let Some(key) = name.strip_prefix(ENV_PREFIX) {
+                config
+                    .apply_env(&key.to_lowercase(), &value)
+                    .with_context(|| format!("invalid environment variable {}", name))?;
+            }
+        }
+        Ok(config)
+    }
+    pub fn parse(toml: &str) -> Result<Self> {
+        let mut config: Self = toml::from_str(toml)?;
+        config.exchanges = config
+            .exchanges
+            .into_iter()
+            .map(|(exchange, config)| (exchange.to_lowercase(), config))
+            .collect();
+        Ok(config)
+    }
+    fn apply_env(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "listen_address" => self.listen_address = parse(value)?,
+            "metrics_address" => self.metrics_address = Some(parse(value)?),
+            "log_format" => {
+                self.log_format =
+                    LogFormat::from_str(value, true).map_err(|error| anyhow!(error))?
+            }
+            "log_level" => self.log_level = value.into(),
+            "pairs" => self.pairs = split(value, ','),
+            // the synthetic pairs contain commas already
+            "synthetic" => self.synthetic = split(value, ';'),
+            "instruments" => self.instruments = Some(value.into()),
+            "binance_stream" => {
+                self.binance_stream =
+                    BinanceStream::from_str(value, true).map_err(|error| anyhow!(error))?
+            }
+            "stale_after_ms" => self.stale_after_ms = parse(value)?,
+            "channel_buffer_size" => self.channel_buffer_size = parse(value)?,
+            "recorder_directory" => self.get_recorder().directory = value.into(),
+            "recorder_max_file_mb" => self.get_recorder().max_file_mb = parse(value)?,
+            "recorder_rotate_after_minutes" => {
+                self.get_recorder().rotate_after_minutes = parse(value)?
+            }
+            "export_directory" => self.get_export().directory = value.into(),
+            "export_formats" => {
+                self.get_export().formats = split(value, ',')
+                    .iter()
+                    .map(|format| ExportFormat::from_str(format, true))
+                    .collect::<Result<_, _>>()
+                    .map_err(|error| anyhow!(error))?
+            }
+            "export_depth" => self.get_export().depth = parse(value)?,
+            "export_rotate_after_minutes" => self.get_export().rotate_after_minutes = parse(value)?,
+            _ => {
+                // like kraken_depth
+                let (exchange, setting) = get_exchange_names()
+                    .into_iter()
+                    .find_map(|exchange| {
+                        let setting = key.strip_prefix(exchange)?.strip_prefix('_')?;
+                        Some((exchange, setting))
+                    })
+                    .ok_or_else(|| anyhow!("{} is not a setting", key))?;
+                let config = self.exchanges.entry(exchange.into()).or_default();
+                match setting {
+                    "enabled" => config.enabled = Some(parse(value)?),
+                    "websocket_url" => config.websocket_url = Some(value.into()),
+                    "rest_url" => config.rest_url = Some(value.into()),
+                    "depth" => config.depth = Some(parse(value)?),
+                    "update_speed_ms" => config.update_speed_ms = Some(parse(value)?),
+                    "taker_fee_bps" => config.taker_fee_bps = Some(parse(value)?),
+                    "stale_after_ms" => config.stale_after_ms = Some(parse(value)?),
+                    _ => return Err(anyhow!("{} is not a setting of {}", setting, exchange)),
+                }
+            }
+        }
+        Ok(())
+    }
+    // any recorder setting turns the recorder on
+    fn get_recorder(&mut self) -> &mut RecorderSettings {
+        self.recorder.get_or_insert_with(RecorderSettings::default)
+    }
+    // any export setting turns the export on
+    fn get_export(&mut self) -> &mut ExportSettings {
+        self.export.get_or_insert_with(ExportSettings::default)
+    }
+    // catches at startup what would otherwise only show up as exchanges never sending anything
+    pub fn validate(&self) -> Result<()> {
+        for exchange in self.exchanges.keys() {
+            if !get_exchange_names().contains(&exchange.as_str()) {
+                return Err(anyhow!(
+                    "{} is not a known exchange, known exchanges are {}",
+                    exchange,
+                    get_exchange_names().join(", ")
+                ));
+            }
+        }
+        if self.pairs.is_empty() && self.synthetic.is_empty() {
+            return Err(anyhow!(
+                "no pair to aggregate, set pairs in the config or use --pair"
+            ));
+        }
+        logging::get_filter(&self.log_level)?;
+        if self.stale_after_ms == 0 {
+            return Err(anyhow!("stale_after_ms must be positive"));
+        }
+        if self.metrics_address == Some(self.listen_address) {
+            return Err(anyhow!(
+                "metrics_address must be different from listen_address"
+            ));
+        }
+        if self.channel_buffer_size == 0 {
+            return Err(anyhow!("channel_buffer_size must be positive"));
+        }
+        if let Some(recorder) = &self.recorder {
+            if recorder.max_file_mb == 0 || recorder.rotate_after_minutes == 0 {
+                return Err(anyhow!(
+                    "max_file_mb and rotate_after_minutes of the recorder must be positive"
+                ));
+            }
+        }
+        if let Some(export) = &self.export {
+            if export.formats.is_empty() {
+                return Err(anyhow!("the export needs at least one format"));
+            }
+            if export.depth == 0 || export.depth > MAX_DEPTH {
+                return Err(anyhow!(
+                    "depth of the export must be between 1 and {}",
+                    MAX_DEPTH
+                ));
+            }
+            if export.rotate_after_minutes == 0 {
+                return Err(anyhow!(
+                    "rotate_after_minutes of the export must be positive"
+                ));
+            }
+        }
+        for (exchange, config) in &self.exchanges {
+            if config.stale_after_ms == Some(0) {
+                return Err(anyhow!("stale_after_ms of {} must be positive", exchange));
+            }
+            if config
+                .taker_fee_bps
+                .is_some_and(|fee| fee.is_sign_negative())
+            {
+                return Err(anyhow!("taker_fee_bps of {} can't be negative", exchange));
+            }
+        }
+        let settings = self.get_exchange_settings()?;
+        if !settings.iter().any(|(_, settings)| settings.enabled) {
+            return Err(anyhow!("every exchange is disabled"));
+        }
+        Ok(())
+    }
+    pub fn get_exchange_settings(&self) -> Result<Vec<(String, ExchangeSettings)>> {
+        Ok(vec![
+            self.get_settings::<Binance>()?,
+            self.get_settings::<Bitstamp>()?,
+            self.get_settings::<Coinbase>()?,
+            self.get_settings::<Kraken>()?,
+        ])
+    }
+    fn get_settings<X: Exchange>(&self) -> Result<(String, ExchangeSettings)> {
+        let name = X::get_name();
+        let defaults = X::get_default_settings();
+        let settings = match self.exchanges.get(name) {
+            Some(config) => {
+                if config.rest_url.is_some() && defaults.rest_url.is_none() {
+                    return Err(anyhow!("{} doesn't use a rest_url", name));
+                }
+                ExchangeSettings {
+                    enabled: config.enabled.unwrap_or(defaults.enabled),
+                    websocket_url: config
+                        .websocket_url
+                        .clone()
+                        .unwrap_or(defaults.websocket_url),
+                    rest_url: config.rest_url.clone().or(defaults.rest_url),
+                    depth: config.depth.or(defaults.depth),
+                    update_speed_ms: config.update_speed_ms.or(defaults.update_speed_ms),
+                }
+            }
+            None => defaults,
+        };
+        check_url(&settings.websocket_url, &["ws", "wss"])
+            .and_then(|()| match &settings.rest_url {
+                Some(rest_url) => check_url(rest_url, &["http", "https"]),
+                None => Ok(()),
+            })
+            .and_then(|()| X::validate_settings(&settings))
+            .with_context(|| format!("invalid settings for {}", name))?;
+        Ok((name.into(), settings))
+    }
+    pub fn get_taker_fees(&self) -> TakerFees {
+        self.exchanges
+            .iter()
+            .filter_map(|(exchange, config)| Some((exchange, config.taker_fee_bps?)))
+            .fold(TakerFees::default(), |fees, (exchange, fee_bps)| {
+                fees.with_exchange(exchange.clone(), fee_bps)
+            })
+    }
+    pub fn get_staleness_timeouts(&self) -> StalenessTimeouts {
+        self.exchanges
+            .iter()
+            .filter_map(|(exchange, config)| Some((exchange, config.stale_after_ms?)))
+            .fold(
+                StalenessTimeouts::new(Duration::from_millis(self.stale_after_ms)),
+                |timeouts, (exchange, timeout)| {
+                    timeouts.with_exchange(exchange.clone(), Duration::from_millis(timeout))
+                },
+            )
+    }
+}
+
+pub fn get_exchange_names() -> [&'static str; 4] {
+    [
+        Binance::get_name(),
+        Bitstamp::get_name(),
+        Coinbase::get_name(),
+        Kraken::get_name(),
+    ]
+}
+
+fn parse<T>(value: &str) -> Result<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value
+        .trim()
+        .parse()
+        .map_err(|error| anyhow!("can't parse {}: {}", value, error))
+}
+
+fn split(value: &str, separator: char) -> Vec<String> {
+    value
+        .split(separator)
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn check_url(url: &str, schemes: &[&str]) -> Result<()> {
+    match url.split_once("://") {
+        Some((scheme, host)) if schemes.contains(&scheme) && !host.is_empty() => Ok(()),
+        _ => Err(anyhow!(
+            "{} is not a {} url",
+            url,
+            schemes
+                .iter()
+                .map(|scheme| format!("{}://", scheme))
+                .collect::<Vec<_>>()
+                .join(" or ")
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    const CONFIG: &str = r#"
+        listen_address = "127.0.0.1:9000"
+        pairs = ["ethbtc", "btcusdt"]
+        binance_stream = "diff"
+        channel_buffer_size = 500
+
+        [exchanges.binance]
+        depth = 20
+        taker_fee_bps = 7.5
+
+        [exchanges.coinbase]
+        enabled = false
+
+        [exchanges.kraken]
+        websocket_url = "wss://kraken.example.com"
+        stale_after_ms = 2000
+    "#;
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse(CONFIG).unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.listen_address, "127.0.0.1:9000".parse().unwrap());
+        assert_eq!(config.binance_stream, BinanceStream::Diff);
+        assert_eq!(config.stale_after_ms, DEFAULT_STALE_AFTER_MS);
+
+        let settings = config.get_exchange_settings().unwrap();
+        assert_eq!(
+            settings[0],
+            (
+                "binance".into(),
+                Binance::get_default_settings().with_depth(20)
+            )
+        );
+        assert!(!settings[2].1.enabled);
+        assert_eq!(settings[3].1.websocket_url, "wss://kraken.example.com");
+        assert_eq!(config.exchanges["binance"].taker_fee_bps, Some(dec!(7.5)));
+
+        assert!(Config::parse("listen_adress = \"127.0.0.1:9000\"").is_err());
+        assert!(Config::parse("[exchanges.kraken]\ndepth = \"ten\"").is_err());
+    }
+
+    #[test]
+    fn test_env() {
+        let config = Config::load(
+            None,
+            vars(&[
+                ("ORDERBOOK_PAIRS", "ethbtc, btcusdt"),
+                ("ORDERBOOK_METRICS_ADDRESS", "0.0.0.0:9000"),
+                ("ORDERBOOK_LOG_FORMAT", "json"),
+                ("ORDERBOOK_SYNTHETIC", "etheur=ethbtc,btceur"),
+                ("ORDERBOOK_BINANCE_STREAM", "Diff"),
+                ("ORDERBOOK_KRAKEN_TAKER_FEE_BPS", "26"),
+                ("ORDERBOOK_BITSTAMP_ENABLED", "false"),
+                ("ORDERBOOK_RECORDER_DIRECTORY", "/var/captures"),
+                ("ORDERBOOK_EXPORT_FORMATS", "parquet"),
+                ("PATH", "/usr/bin"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.pairs, vec!["ethbtc", "btcusdt"]);
+        assert_eq!(
+            config.metrics_address,
+            Some("0.0.0.0:9000".parse().unwrap())
+        );
+        assert_eq!(config.log_format, LogFormat::Json);
+        assert_eq!(config.synthetic, vec!["etheur=ethbtc,btceur"]);
+        assert_eq!(config.binance_stream, BinanceStream::Diff);
+        assert_eq!(config.exchanges["kraken"].taker_fee_bps, Some(dec!(26)));
+        assert_eq!(config.exchanges["bitstamp"].enabled, Some(false));
+        assert_eq!(
+            config.recorder,
+            Some(RecorderSettings::new("/var/captures".into()))
+        );
+        assert_eq!(config.export.unwrap().formats, vec![ExportFormat::Parquet]);
+
+        let error = Config::load(None, vars(&[("ORDERBOOK_KRAKEN_DEPT", "25")])).unwrap_err();
+        assert!(format!("{:#}", error).contains("ORDERBOOK_KRAKEN_DEPT"));
+        assert!(Config::load(None, vars(&[("ORDERBOOK_STALE_AFTER_MS", "soon")])).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let invalid = |toml: &str| {
+            let config = Config::parse(&format!("pairs = [\"ethbtc\"]\n{}", toml)).unwrap();
+            format!("{:#}", config.validate().unwrap_err())
+        };
+        assert!(invalid("[exchanges.ftx]").contains("ftx is not a known exchange"));
+        assert!(
+            invalid("[exchanges.binance]\ndepth = 15").contains("depth must be one of 5, 10, 20")
+        );
+        assert!(invalid("[exchanges.bitstamp]\ndepth = 10").contains("depth can't be set"));
+        assert!(
+            invalid("[exchanges.kraken]\nrest_url = \"https://kraken.com\"")
+                .contains("kraken doesn't use a rest_url")
+        );
+        assert!(
+            invalid("[exchanges.coinbase]\nwebsocket_url = \"https://coinbase.com\"")
+                .contains("not a ws:// or wss:// url")
+        );
+        assert!(invalid("channel_buffer_size = 0").contains("channel_buffer_size"));
+        assert!(invalid("log_level = \"crypto=loud\"").contains("invalid log level"));
+        assert!(invalid("[recorder]\nmax_file_mb = 0").contains("recorder must be positive"));
+        assert!(invalid("[export]\nformats = []").contains("at least one format"));
+        assert!(invalid("[export]\ndepth = 101").contains("between 1 and 100"));
+        assert!(invalid(
+            "[exchanges.binance]\nenabled = false\n[exchanges.bitstamp]\nenabled = false\n\
+             [exchanges.coinbase]\nenabled = false\n[exchanges.kraken]\nenabled = false"
+        )
+        .contains("every exchange is disabled"));
+
+        let config = Config::parse("").unwrap();
+        assert!(config.validate().is_err());
+
+        let config = Config::parse(include_str!("../config.example.toml")).unwrap();
+        assert!(config.validate().is_ok());
+    }
+}
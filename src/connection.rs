@@ -0,0 +1,258 @@
+use crate::{
+    metrics,
+    order_book::OrderBook,
+    recorder::{FrameSource, Recorder},
+};
+use anyhow::Result;
+use rand::Rng;
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::{sync::mpsc::Sender, time::sleep};
+use tracing::{debug, error, info, info_span, warn, Instrument};
+
+const BACKOFF_BASE_DELAY: Duration = Duration::from_millis(500);
+const BACKOFF_MAX_DELAY: Duration = Duration::from_secs(60);
+const BACKOFF_MAX_ATTEMPTS: u32 = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConnectionState {
+    Connecting,
+    Connected,
+    Reconnecting { attempt: u32 },
+    Failed,
+}
+
+// connection state of each exchange, shared between the exchange tasks and the service
+#[derive(Clone, Default)]
+pub struct ConnectionStates(Arc<RwLock<HashMap<&'static str, ConnectionState>>>);
+
+impl ConnectionStates {
+    pub fn get(&self, exchange: &str) -> Option<ConnectionState> {
+        self.0.read().unwrap().get(exchange).copied()
+    }
+    pub fn get_all(&self) -> Vec<(&'static str, ConnectionState)> {
+        let mut states: Vec<(&'static str, ConnectionState)> = self
+            .0
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(exchange, state)| (*exchange, *state))
+            .collect();
+        states.sort_by_key(|(exchange, _)| *exchange);
+        states
+    }
+    fn set(&self, exchange: &'static str, state: ConnectionState) {
+        if self.get(exchange) != Some(state) {
+            self.0.write().unwrap().insert(exchange, state);
+        }
+    }
+}
+
+// what an exchange session needs to publish its order books
+#[derive(Clone)]
+pub struct Connection {
+    exchange: &'static str,
+    pair: String,
+    sender: Sender<OrderBook>,
+    states: ConnectionStates,
+    // captures the raw frames when set
+    recorder: Option<Recorder>,
+}
+
+impl Connection {
+    pub fn new(
+        exchange: &'static str,
+        sender: Sender<OrderBook>,
+        states: ConnectionStates,
+    ) -> Self {
+        Self {
+            exchange,
+            pair: String::new(),
+            sender,
+            states,
+            recorder: None,
+        }
+    }
+    pub fn with_pair(self, pair: &str) -> Self {
+        Self {
+            pair: pair.into(),
+            ..self
+        }
+    }
+    pub fn with_recorder(self, recorder: Option<Recorder>) -> Self {
+        Self { recorder, ..self }
+    }
+    // every text frame, whether it updates the book or not
+    pub fn record_message(&self, frame: &str) {
+        metrics::record_message(self.exchange, &self.pair);
+        if let Some(recorder) = &self.recorder {
+            recorder.record(self.exchange, &self.pair, FrameSource::Websocket, frame);
+        }
+    }
+    // the body of a rest snapshot, so the diff streams applied on it can be replayed
+    pub fn record_snapshot(&self, body: &str) {
+        if let Some(recorder) = &self.recorder {
+            recorder.record(self.exchange, &self.pair, FrameSource::Rest, body);
+        }
+    }
+    // a frame that should have been a book update
+    pub fn record_parse_failure(&self) {
+        metrics::record_parse_failure(self.exchange, &self.pair);
+    }
+    // the exchange counts as connected once it delivers an order book
+    pub async fn send(&self, mut order_book: OrderBook) -> Result<()> {
+        order_book.set_received_at(Instant::now());
+        order_book.set_received_time(SystemTime::now());
+        self.sender.send(order_book).await?;
+        self.states.set(self.exchange, ConnectionState::Connected);
+        Ok(())
+    }
+}
+
+// exponential backoff with jitter between reconnection attempts
+#[derive(Clone, Copy)]
+pub struct Backoff {
+    base_delay: Duration,
+    max_delay: Duration,
+    max_attempts: u32,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff::new(BACKOFF_BASE_DELAY, BACKOFF_MAX_DELAY, BACKOFF_MAX_ATTEMPTS)
+    }
+}
+
+impl Backoff {
+    pub fn new(base_delay: Duration, max_delay: Duration, max_attempts: u32) -> Self {
+        Self {
+            base_delay,
+            max_delay,
+            max_attempts,
+        }
+    }
+    // a random delay between half and all of the exponential delay, so exchanges don't retry in lockstep
+    fn get_delay(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        rand::thread_rng().gen_range(exponential / 2..=exponential)
+    }
+}
+
+// runs the sessions of an exchange until they fail too many times in a row
+pub async fn supervise<F, Fut>(connection: Connection, backoff: Backoff, session: F)
+where
+    F: FnMut(Connection) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    // everything the sessions log belongs to the connection
+    let span = info_span!(
+        "connection",
+        exchange = connection.exchange,
+        pair = %connection.pair
+    );
+    run_sessions(connection, backoff, session)
+        .instrument(span)
+        .await
+}
+
+async fn run_sessions<F, Fut>(connection: Connection, backoff: Backoff, mut session: F)
+where
+    F: FnMut(Connection) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let Connection {
+        exchange, states, ..
+    } = connection.clone();
+    let mut attempt = 0;
+
+    states.set(exchange, ConnectionState::Connecting);
+
+    // stops once nobody listens to the order books anymore
+    while !connection.sender.is_closed() {
+        debug!(attempt, "connecting");
+        let result = session(connection.clone()).await;
+
+        // a session that delivered data resets the backoff
+        if states.get(exchange) == Some(ConnectionState::Connected) {
+            attempt = 0;
+        }
+        attempt += 1;
+
+        if attempt > backoff.max_attempts {
+            error!(
+                failures = attempt - 1,
+                "connection failed too many times, giving up"
+            );
+            states.set(exchange, ConnectionState::Failed);
+            return;
+        }
+
+        let delay = backoff.get_delay(attempt);
+        match result {
+            Ok(()) => info!(attempt, ?delay, "connection closed, reconnecting"),
+            Err(error) => warn!(%error, attempt, ?delay, "connection failed, reconnecting"),
+        }
+        states.set(exchange, ConnectionState::Reconnecting { attempt });
+        metrics::record_reconnect(exchange, &connection.pair);
+        sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use tokio::sync::mpsc::channel;
+
+    #[test]
+    fn test_backoff_delay() {
+        let backoff = Backoff::new(Duration::from_millis(100), Duration::from_millis(1000), 10);
+
+        for _ in 0..100 {
+            let first = backoff.get_delay(1);
+            assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+
+            let third = backoff.get_delay(3);
+            assert!(third >= Duration::from_millis(200) && third <= Duration::from_millis(400));
+
+            // capped to the max delay
+            let tenth = backoff.get_delay(10);
+            assert!(tenth >= Duration::from_millis(500) && tenth <= Duration::from_millis(1000));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_supervise() {
+        let (sender, mut receiver) = channel::<OrderBook>(100);
+        let states = ConnectionStates::default();
+        let connection = Connection::new("binance", sender, states.clone());
+        let backoff = Backoff::new(Duration::from_millis(1), Duration::from_millis(4), 3);
+
+        let mut sessions = 0;
+        supervise(connection, backoff, |connection| {
+            sessions += 1;
+            let delivers = sessions == 2;
+            async move {
+                if delivers {
+                    connection.send(OrderBook::new("binance")).await?;
+                    Ok(())
+                } else {
+                    Err(anyhow!("connection refused"))
+                }
+            }
+        })
+        .await;
+
+        // the second session resets the attempts, then three more fail before giving up
+        assert_eq!(sessions, 5);
+        assert_eq!(states.get("binance"), Some(ConnectionState::Failed));
+        assert!(receiver.recv().await.is_some());
+    }
+}
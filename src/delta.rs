@@ -0,0 +1,152 @@
+use crate::proto::{self, BookDelta, Level, LevelChange, LevelDelta, Summary};
+use std::collections::{HashMap, HashSet};
+
+// the changes turning the previous summary sent to a client into the new one
+pub fn get_book_delta(previous: Option<&Summary>, summary: &Summary) -> BookDelta {
+    let (previous_bids, previous_asks, previous_sequence) = match previous {
+        Some(previous) => (&previous.bids[..], &previous.asks[..], previous.sequence),
+        None => (&[][..], &[][..], 0),
+    };
+
+    let mut levels = get_side_deltas(proto::Side::Bid, previous_bids, &summary.bids);
+    levels.extend(get_side_deltas(
+        proto::Side::Ask,
+        previous_asks,
+        &summary.asks,
+    ));
+
+    BookDelta {
+        snapshot: previous.is_none(),
+        sequence: summary.sequence,
+        previous_sequence,
+        levels,
+        built_at_us: summary.built_at_us,
+    }
+}
+
+fn get_side_deltas(side: proto::Side, previous: &[Level], current: &[Level]) -> Vec<LevelDelta> {
+    let key = |level: &Level| (level.exchange.clone(), level.exact_price.clone());
+    let previous_levels: HashMap<(String, String), &Level> =
+        previous.iter().map(|level| (key(level), level)).collect();
+    let current_keys: HashSet<(String, String)> = current.iter().map(key).collect();
+
+    let level_delta = |change: LevelChange, level: Level| LevelDelta {
+        side: side.into(),
+        change: change.into(),
+        level: Some(level),
+    };
+
+    // deletes first, so a client with a fixed depth never holds more levels than asked for
+    let deletes = previous
+        .iter()
+        .filter(|level| !current_keys.contains(&key(level)))
+        .map(|level| {
+            level_delta(
+                LevelChange::Delete,
+                Level {
+                    amount: 0.0,
+                    exact_amount: "0".into(),
+                    ..level.clone()
+                },
+            )
+        });
+    let inserts_and_updates =
+        current
+            .iter()
+            .filter_map(|level| match previous_levels.get(&key(level)) {
+                None => Some(level_delta(LevelChange::Insert, level.clone())),
+                // a consolidated level can change its venues without changing its amount
+                Some(previous) if *previous != level => {
+                    Some(level_delta(LevelChange::Update, level.clone()))
+                }
+                Some(_) => None,
+            });
+
+    deletes.chain(inserts_and_updates).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(exchange: &str, price: &str, amount: &str) -> Level {
+        Level {
+            exchange: exchange.into(),
+            price: price.parse().unwrap(),
+            amount: amount.parse().unwrap(),
+            exact_price: price.into(),
+            exact_amount: amount.into(),
+            ..Default::default()
+        }
+    }
+
+    fn changes(delta: &BookDelta) -> Vec<String> {
+        delta
+            .levels
+            .iter()
+            .map(|level_delta| {
+                let level = level_delta.level.as_ref().unwrap();
+                format!(
+                    "{:?} {:?} {} {} {}",
+                    level_delta.side(),
+                    level_delta.change(),
+                    level.exchange,
+                    level.exact_price,
+                    level.exact_amount
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_book_delta() {
+        let first = Summary {
+            sequence: 1,
+            bids: vec![
+                level("binance", "0.0679", "1"),
+                level("bitstamp", "0.0679", "2"),
+            ],
+            asks: vec![level("binance", "0.068", "1")],
+            ..Default::default()
+        };
+
+        let snapshot = get_book_delta(None, &first);
+        assert!(snapshot.snapshot);
+        assert_eq!(snapshot.sequence, 1);
+        assert_eq!(snapshot.previous_sequence, 0);
+        assert_eq!(
+            changes(&snapshot),
+            vec![
+                "Bid Insert binance 0.0679 1",
+                "Bid Insert bitstamp 0.0679 2",
+                "Ask Insert binance 0.068 1",
+            ]
+        );
+
+        // the same price on another exchange is another level
+        let second = Summary {
+            sequence: 4,
+            bids: vec![
+                level("binance", "0.0679", "1"),
+                level("bitstamp", "0.0679", "3"),
+            ],
+            asks: vec![level("kraken", "0.0681", "5")],
+            ..Default::default()
+        };
+
+        let delta = get_book_delta(Some(&first), &second);
+        assert!(!delta.snapshot);
+        assert_eq!(delta.sequence, 4);
+        assert_eq!(delta.previous_sequence, 1);
+        assert_eq!(
+            changes(&delta),
+            vec![
+                "Bid Update bitstamp 0.0679 3",
+                "Ask Delete binance 0.068 0",
+                "Ask Insert kraken 0.0681 5",
+            ]
+        );
+
+        assert!(get_book_delta(Some(&second), &second).levels.is_empty());
+    }
+}
@@ -1,7 +1,117 @@
-use crate::order_book::OrderBook;
-use tokio::sync::mpsc::Sender;
+use crate::{
+    connection::{supervise, Backoff, Connection},
+    order_book::OrderBook,
+};
+use anyhow::{anyhow, Result};
+
+// where to reach an exchange and how much of its book to ask for
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExchangeSettings {
+    pub enabled: bool,
+    pub websocket_url: String,
+    // snapshots for the exchanges streaming diffs
+    pub rest_url: Option<String>,
+    // levels per side, for the exchanges letting the subscriber choose
+    pub depth: Option<usize>,
+    // milliseconds between two updates, for the exchanges letting the subscriber choose
+    pub update_speed_ms: Option<u64>,
+}
+
+impl ExchangeSettings {
+    pub fn new(websocket_url: &str) -> Self {
+        Self {
+            enabled: true,
+            websocket_url: websocket_url.into(),
+            rest_url: None,
+            depth: None,
+            update_speed_ms: None,
+        }
+    }
+    pub fn with_rest_url(self, rest_url: &str) -> Self {
+        Self {
+            rest_url: Some(rest_url.into()),
+            ..self
+        }
+    }
+    pub fn with_depth(self, depth: usize) -> Self {
+        Self {
+            depth: Some(depth),
+            ..self
+        }
+    }
+    pub fn with_update_speed_ms(self, update_speed_ms: u64) -> Self {
+        Self {
+            update_speed_ms: Some(update_speed_ms),
+            ..self
+        }
+    }
+    // the values an exchange accepts for a setting, none when it can't be chosen
+    pub fn check<T: PartialEq + std::fmt::Display>(
+        name: &str,
+        value: Option<T>,
+        allowed: &[T],
+    ) -> Result<()> {
+        match value {
+            Some(value) if !allowed.contains(&value) => {
+                let allowed: Vec<String> = allowed.iter().map(T::to_string).collect();
+                match allowed.is_empty() {
+                    true => Err(anyhow!("{} can't be set", name)),
+                    false => Err(anyhow!(
+                        "{} must be one of {}, got {}",
+                        name,
+                        allowed.join(", "),
+                        value
+                    )),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
 #[tonic::async_trait]
 pub trait Exchange {
     fn get_name() -> &'static str;
-    async fn get_order_book(pair: String, sender: Sender<OrderBook>) -> ();
+    fn get_default_settings() -> ExchangeSettings;
+    // the depth and update speed the exchange accepts
+    fn validate_settings(settings: &ExchangeSettings) -> Result<()> {
+        ExchangeSettings::check::<usize>("depth", settings.depth, &[])?;
+        ExchangeSettings::check::<u64>("update_speed_ms", settings.update_speed_ms, &[])
+    }
+    // a single session with the exchange for its symbol of a pair, returns when the connection is
+    // closed or fails
+    async fn connect(
+        symbol: String,
+        settings: ExchangeSettings,
+        connection: Connection,
+    ) -> Result<()>;
+}
+
+// what a websocket text frame of an exchange is about
+#[derive(Debug)]
+pub enum Frame {
+    Book(OrderBook),
+    // asks the client to reconnect, usually before a maintenance
+    Reconnect,
+    // subscription confirmations, heartbeats
+    Ignored,
+}
+
+// the exchanges sending their whole book in every frame, so a frame is parsed without the ones
+// before it and their captures can be replayed
+pub trait SnapshotFrames: Exchange {
+    // an error for the frames that should have been a book update
+    fn parse_frame(frame: &str) -> Result<Frame>;
+}
+
+// keeps the exchange connected, reconnecting with a backoff
+pub async fn get_order_book<X: Exchange>(
+    symbol: String,
+    settings: ExchangeSettings,
+    connection: Connection,
+) {
+    supervise(connection, Backoff::default(), |connection| {
+        X::connect(symbol.clone(), settings.clone(), connection)
+    })
+    .await
 }
@@ -0,0 +1,568 @@
+use crate::proto::{Level, Summary, SummaryRequest};
+use anyhow::{Context, Result};
+use arrow_array::{
+    ArrayRef, Float64Array, RecordBatch, StringArray, TimestampMicrosecondArray, UInt32Array,
+    UInt64Array,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use futures_util::{Stream, StreamExt};
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+use serde::Deserialize;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fs::{self, File},
+    path::PathBuf,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::task::{spawn, spawn_blocking, JoinHandle};
+use tonic::Status;
+use tracing::{error, info, warn};
+
+const DEFAULT_EXPORT_DEPTH: usize = 10;
+const DEFAULT_ROTATE_AFTER_MINUTES: u64 = 10;
+const MICROSECONDS_PER_HOUR: u64 = 3_600_000_000;
+
+// rows of a parquet row group, written when it is full or the file is closed
+const PARQUET_ROW_GROUP_SIZE: usize = 100_000;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    // flushed with every summary
+    Csv,
+    // readable once the file is closed
+    Parquet,
+}
+
+impl ExportFormat {
+    fn get_extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+}
+
+// where the summaries go and how much of them
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExportSettings {
+    pub directory: PathBuf,
+    pub formats: Vec<ExportFormat>,
+    // levels per side
+    pub depth: usize,
+    // a new file is started every hour, and within the hour after this many minutes
+    pub rotate_after_minutes: u64,
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("summaries"),
+            formats: vec![ExportFormat::Csv, ExportFormat::Parquet],
+            depth: DEFAULT_EXPORT_DEPTH,
+            rotate_after_minutes: DEFAULT_ROTATE_AFTER_MINUTES,
+        }
+    }
+}
+
+impl ExportSettings {
+    #[cfg(test)]
+    pub fn new(directory: PathBuf) -> Self {
+        Self {
+            directory,
+            ..Self::default()
+        }
+    }
+    // the summaries are exported like a BookSummary stream with this request gets them
+    pub fn get_request(&self) -> SummaryRequest {
+        SummaryRequest {
+            depth: self.depth as u32,
+            ..SummaryRequest::default()
+        }
+    }
+}
+
+// the export tasks of the pairs and the thread writing their summaries
+pub struct Export {
+    tasks: Vec<JoinHandle<()>>,
+    writer: thread::JoinHandle<()>,
+}
+
+impl Export {
+    // like a client subscribed to every pair
+    pub fn start<S>(settings: ExportSettings, streams: Vec<(String, S)>) -> Result<Self>
+    where
+        S: Stream<Item = Result<Summary, Status>> + Unpin + Send + 'static,
+    {
+        let (exporter, writer) = Exporter::new(settings)?;
+        let tasks = streams
+            .into_iter()
+            .map(|(pair, summaries)| spawn(exporter.clone().export(pair, summaries)))
+            .collect();
+        Ok(Self { tasks, writer })
+    }
+    // closes the files, a parquet file can't be read before
+    pub async fn stop(self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+        for task in self.tasks {
+            let _ = task.await;
+        }
+        // the writer closes the files once the exporters are gone
+        let writer = self.writer;
+        let _ = spawn_blocking(move || writer.join()).await;
+    }
+}
+
+// sends the summaries of a pair to the writer thread, which keeps the tasks off the disk
+#[derive(Clone)]
+struct Exporter {
+    sender: Sender<(String, Summary)>,
+}
+
+impl Exporter {
+    fn new(settings: ExportSettings) -> Result<(Self, thread::JoinHandle<()>)> {
+        fs::create_dir_all(&settings.directory).with_context(|| {
+            format!(
+                "can't create the export directory {}",
+                settings.directory.display()
+            )
+        })?;
+        let (sender, receiver) = channel();
+        let writer = thread::Builder::new()
+            .name("exporter".into())
+            .spawn(move || write_summaries(settings, receiver))?;
+        Ok((Self { sender }, writer))
+    }
+    // runs until the stream of the pair ends
+    async fn export<S>(self, pair: String, mut summaries: S)
+    where
+        S: Stream<Item = Result<Summary, Status>> + Unpin,
+    {
+        info!(pair = %pair, "exporting the summaries");
+        while let Some(summary) = summaries.next().await {
+            match summary {
+                // the writer only stops once every exporter is dropped
+                Ok(summary) => {
+                    let _ = self.sender.send((pair.clone(), summary));
+                }
+                // the stream recovers from a lag by skipping the summaries it missed
+                Err(status) => warn!(pair = %pair, error = %status.message(), "summaries skipped"),
+            }
+        }
+    }
+}
+
+// a level of a summary, the files have a row per level
+#[derive(Debug, PartialEq)]
+struct SummaryRow<'a> {
+    built_at_us: u64,
+    sequence: u64,
+    spread: f64,
+    // the decimals as the exchanges sent them, the floats can't be reconciled with their prices
+    exact_spread: &'a str,
+    side: &'static str,
+    // 0 for the best price
+    level: u32,
+    exchange: &'a str,
+    price: f64,
+    exact_price: &'a str,
+    amount: f64,
+    exact_amount: &'a str,
+}
+
+impl<'a> SummaryRow<'a> {
+    fn from_summary(summary: &'a Summary) -> Vec<Self> {
+        let side = |side: &'static str, levels: &'a [Level]| {
+            levels.iter().enumerate().map(move |(index, level)| Self {
+                built_at_us: summary.built_at_us,
+                sequence: summary.sequence,
+                spread: summary.spread,
+                exact_spread: &summary.exact_spread,
+                side,
+                level: index as u32,
+                exchange: &level.exchange,
+                price: level.price,
+                exact_price: &level.exact_price,
+                amount: level.amount,
+                exact_amount: &level.exact_amount,
+            })
+        };
+        side("bid", &summary.bids)
+            .chain(side("ask", &summary.asks))
+            .collect()
+    }
+    fn to_csv_record(&self) -> [String; 11] {
+        [
+            get_time(self.built_at_us).to_rfc3339_opts(chrono::SecondsFormat::Micros, true),
+            self.sequence.to_string(),
+            self.spread.to_string(),
+            self.exact_spread.into(),
+            self.side.into(),
+            self.level.to_string(),
+            self.exchange.into(),
+            self.price.to_string(),
+            self.exact_price.into(),
+            self.amount.to_string(),
+            self.exact_amount.into(),
+        ]
+    }
+}
+
+const CSV_HEADER: [&str; 11] = [
+    "timestamp",
+    "sequence",
+    "spread",
+    "exact_spread",
+    "side",
+    "level",
+    "exchange",
+    "price",
+    "exact_price",
+    "amount",
+    "exact_amount",
+];
+
+fn get_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new(
+            "timestamp",
+            DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+            false,
+        ),
+        Field::new("sequence", DataType::UInt64, false),
+        Field::new("spread", DataType::Float64, false),
+        Field::new("exact_spread", DataType::Utf8, false),
+        Field::new("side", DataType::Utf8, false),
+        Field::new("level", DataType::UInt32, false),
+        Field::new("exchange", DataType::Utf8, false),
+        Field::new("price", DataType::Float64, false),
+        Field::new("exact_price", DataType::Utf8, false),
+        Field::new("amount", DataType::Float64, false),
+        Field::new("exact_amount", DataType::Utf8, false),
+    ]))
+}
+
+fn to_record_batch(rows: &[SummaryRow]) -> Result<RecordBatch> {
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(
+            TimestampMicrosecondArray::from_iter_values(
+                rows.iter().map(|row| row.built_at_us as i64),
+            )
+            .with_timezone("UTC"),
+        ),
+        Arc::new(UInt64Array::from_iter_values(
+            rows.iter().map(|row| row.sequence),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            rows.iter().map(|row| row.spread),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|row| row.exact_spread),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|row| row.side),
+        )),
+        Arc::new(UInt32Array::from_iter_values(
+            rows.iter().map(|row| row.level),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|row| row.exchange),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            rows.iter().map(|row| row.price),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|row| row.exact_price),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            rows.iter().map(|row| row.amount),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|row| row.exact_amount),
+        )),
+    ];
+    Ok(RecordBatch::try_new(get_schema(), columns)?)
+}
+
+fn get_time(time_us: u64) -> DateTime<Utc> {
+    (UNIX_EPOCH + Duration::from_micros(time_us)).into()
+}
+
+// the files of a pair for an hour, like pair=ethbtc/date=2023-04-22/hour=12/part-<opened>.csv
+struct Partition {
+    hour: u64,
+    opened_at: Instant,
+    csv: Option<csv::Writer<File>>,
+    parquet: Option<ArrowWriter<File>>,
+}
+
+impl Partition {
+    fn open(settings: &ExportSettings, pair: &str, built_at_us: u64) -> Result<Self> {
+        let hour = get_time(built_at_us);
+        let directory = settings
+            .directory
+            .join(format!("pair={}", pair))
+            .join(hour.format("date=%Y-%m-%d").to_string())
+            .join(hour.format("hour=%H").to_string());
+        fs::create_dir_all(&directory)
+            .with_context(|| format!("can't create {}", directory.display()))?;
+        let opened_time: DateTime<Utc> = SystemTime::now().into();
+        let create = |format: ExportFormat| {
+            let path = directory.join(format!(
+                "part-{}.{}",
+                opened_time.format("%Y%m%dT%H%M%S%.6fZ"),
+                format.get_extension()
+            ));
+            info!(path = %path.display(), "export file opened");
+            File::create(&path).with_context(|| format!("can't create {}", path.display()))
+        };
+
+        let mut partition = Self {
+            hour: built_at_us / MICROSECONDS_PER_HOUR,
+            opened_at: Instant::now(),
+            csv: None,
+            parquet: None,
+        };
+        for format in &settings.formats {
+            match format {
+                ExportFormat::Csv => {
+                    let mut writer = csv::Writer::from_writer(create(*format)?);
+                    writer.write_record(CSV_HEADER)?;
+                    partition.csv = Some(writer);
+                }
+                ExportFormat::Parquet => {
+                    let properties = WriterProperties::builder()
+                        .set_compression(Compression::SNAPPY)
+                        .set_max_row_group_size(PARQUET_ROW_GROUP_SIZE)
+                        .build();
+                    partition.parquet = Some(ArrowWriter::try_new(
+                        create(*format)?,
+                        get_schema(),
+                        Some(properties),
+                    )?);
+                }
+            }
+        }
+        Ok(partition)
+    }
+    fn is_current(&self, settings: &ExportSettings, built_at_us: u64) -> bool {
+        self.hour == built_at_us / MICROSECONDS_PER_HOUR
+            && self.opened_at.elapsed() < Duration::from_secs(settings.rotate_after_minutes * 60)
+    }
+    fn write(&mut self, summary: &Summary) -> Result<()> {
+        let rows = SummaryRow::from_summary(summary);
+        if let Some(csv) = &mut self.csv {
+            for row in &rows {
+                csv.write_record(row.to_csv_record())?;
+            }
+            // a crash only loses the summary being written
+            csv.flush()?;
+        }
+        if let Some(parquet) = &mut self.parquet {
+            parquet.write(&to_record_batch(&rows)?)?;
+        }
+        Ok(())
+    }
+    // writes the parquet footer
+    fn close(self) -> Result<()> {
+        if let Some(mut csv) = self.csv {
+            csv.flush()?;
+        }
+        if let Some(parquet) = self.parquet {
+            parquet.close()?;
+        }
+        Ok(())
+    }
+}
+
+fn write_summaries(settings: ExportSettings, receiver: Receiver<(String, Summary)>) {
+    let mut partitions: HashMap<String, Partition> = HashMap::new();
+    while let Ok((pair, summary)) = receiver.recv() {
+        if let Err(error) = write_summary(&settings, &mut partitions, &pair, &summary) {
+            // the summaries of the pair are lost until the next files can be opened
+            error!(%error, pair = %pair, "can't export the summary");
+            partitions.remove(&pair);
+        }
+    }
+    for (pair, partition) in partitions {
+        if let Err(error) = partition.close() {
+            error!(%error, pair = %pair, "can't close the export");
+        }
+    }
+}
+
+fn write_summary(
+    settings: &ExportSettings,
+    partitions: &mut HashMap<String, Partition>,
+    pair: &str,
+    summary: &Summary,
+) -> Result<()> {
+    // an empty book has no rows
+    if summary.bids.is_empty() && summary.asks.is_empty() {
+        return Ok(());
+    }
+    let built_at_us = summary.built_at_us;
+    if partitions
+        .get(pair)
+        .is_some_and(|partition| !partition.is_current(settings, built_at_us))
+    {
+        if let Some(partition) = partitions.remove(pair) {
+            partition.close()?;
+        }
+    }
+    let partition = match partitions.entry(pair.into()) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => entry.insert(Partition::open(settings, pair, built_at_us)?),
+    };
+    partition.write(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::stream;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use std::{env::temp_dir, path::Path};
+    use tokio::time::sleep;
+
+    // every file of a format under a directory, oldest first within a partition
+    fn get_export_files(directory: &Path, format: ExportFormat) -> Result<Vec<PathBuf>> {
+        let mut files = vec![];
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                files.extend(get_export_files(&path, format)?);
+            } else if path
+                .extension()
+                .is_some_and(|extension| extension == format.get_extension())
+            {
+                files.push(path);
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    // 2023-04-22T12:41:26Z
+    const BUILT_AT_US: u64 = 1_682_167_286_000_000;
+
+    fn get_summary(sequence: u64, built_at_us: u64) -> Summary {
+        let level = |exchange: &str, price: f64, exact_price: &str| Level {
+            exchange: exchange.into(),
+            price,
+            exact_price: exact_price.into(),
+            amount: 1.5,
+            exact_amount: "1.50000000".into(),
+            ..Level::default()
+        };
+        Summary {
+            spread: 0.00001,
+            exact_spread: "0.00001".into(),
+            bids: vec![
+                level("binance", 0.06795, "0.06795000"),
+                level("kraken", 0.06794, "0.06794"),
+            ],
+            asks: vec![level("bitstamp", 0.06796, "0.06796")],
+            sequence,
+            built_at_us,
+            ..Summary::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_export() {
+        let directory = temp_dir().join(format!("export-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let settings = ExportSettings::new(directory.clone());
+        assert_eq!(settings.get_request().depth, 10);
+
+        let ethbtc = vec![
+            Ok(get_summary(1, BUILT_AT_US)),
+            Err(Status::internal("Message lagged")),
+            Ok(get_summary(2, BUILT_AT_US + 1)),
+            // an empty book has no rows
+            Ok(Summary {
+                built_at_us: BUILT_AT_US + 2,
+                ..Summary::default()
+            }),
+            Ok(get_summary(3, BUILT_AT_US + MICROSECONDS_PER_HOUR)),
+        ];
+        let btcusdt = vec![Ok(get_summary(1, BUILT_AT_US))];
+        let export = Export::start(
+            settings,
+            vec![
+                ("ethbtc".to_string(), stream::iter(ethbtc)),
+                ("btcusdt".to_string(), stream::iter(btcusdt)),
+            ],
+        )
+        .unwrap();
+        // lets the tasks send their summaries before they are stopped
+        sleep(Duration::from_millis(100)).await;
+        export.stop().await;
+
+        let csv_files = get_export_files(&directory, ExportFormat::Csv).unwrap();
+        let partitions: Vec<String> = csv_files
+            .iter()
+            .map(|file| {
+                let partition = file.parent().unwrap().strip_prefix(&directory).unwrap();
+                partition.to_string_lossy().into()
+            })
+            .collect();
+        assert_eq!(
+            partitions,
+            vec![
+                "pair=btcusdt/date=2023-04-22/hour=12",
+                "pair=ethbtc/date=2023-04-22/hour=12",
+                "pair=ethbtc/date=2023-04-22/hour=13",
+            ]
+        );
+
+        let csv = fs::read_to_string(&csv_files[1]).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 7);
+        assert_eq!(
+            lines[0],
+            "timestamp,sequence,spread,exact_spread,side,level,exchange,price,exact_price,amount,exact_amount"
+        );
+        assert_eq!(
+            lines[1],
+            "2023-04-22T12:41:26.000000Z,1,0.00001,0.00001,bid,0,binance,0.06795,0.06795000,1.5,1.50000000"
+        );
+        assert_eq!(
+            lines[6],
+            "2023-04-22T12:41:26.000001Z,2,0.00001,0.00001,ask,0,bitstamp,0.06796,0.06796,1.5,1.50000000"
+        );
+
+        let parquet_files = get_export_files(&directory, ExportFormat::Parquet).unwrap();
+        assert_eq!(parquet_files.len(), 3);
+        let batches: Vec<RecordBatch> =
+            ParquetRecordBatchReaderBuilder::try_new(File::open(&parquet_files[1]).unwrap())
+                .unwrap()
+                .build()
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap();
+        assert_eq!(batches[0].schema(), get_schema());
+        let rows: usize = batches.iter().map(RecordBatch::num_rows).sum();
+        assert_eq!(rows, 6);
+        let exact_prices = batches[0]
+            .column_by_name("exact_price")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(exact_prices.value(0), "0.06795000");
+        assert_eq!(exact_prices.value(2), "0.06796");
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,309 @@
+use crate::{
+    merged_book::{MergedBook, BASIS_POINTS},
+    order_book::{to_f64, PriceLevel, Side},
+    proto::{FillEstimate, FillRequest, OrderSide, VenueFill},
+};
+use rust_decimal::{prelude::FromPrimitive, Decimal};
+
+const TWO: Decimal = Decimal::from_parts(2, 0, 0, false, 0);
+
+// what an exchange fills of the order
+struct VenueTotal {
+    exchange: &'static str,
+    quantity: Decimal,
+    notional: Decimal,
+}
+
+// the quantity and notional of the order, none when one of them doesn't fit a decimal
+pub fn get_order_size(request: &FillRequest) -> Option<(Decimal, Decimal)> {
+    Some((
+        Decimal::from_f64(request.quantity)?,
+        Decimal::from_f64(request.notional)?,
+    ))
+}
+
+// a market order takes the best levels first, whichever exchange they are on
+pub fn get_fill_estimate(
+    merged_book: &MergedBook,
+    request: &FillRequest,
+    (quantity, notional): (Decimal, Decimal),
+) -> FillEstimate {
+    let is_requested = |exchange: &str| {
+        request.exchanges.is_empty()
+            || request
+                .exchanges
+                .iter()
+                .any(|requested| requested.eq_ignore_ascii_case(exchange))
+    };
+    let price = |side: Side, level: &PriceLevel| {
+        if request.fee_adjusted {
+            merged_book.get_taker_fees().get_adjusted_price(side, level)
+        } else {
+            level.price
+        }
+    };
+    let best_price = |side: Side| {
+        merged_book
+            .get_levels(side, request.fee_adjusted)
+            .iter()
+            .find(|level| is_requested(level.exchange))
+            .map(|level| price(side, level))
+    };
+    let mid_price = match (best_price(Side::Bid), best_price(Side::Ask)) {
+        (Some(bid), Some(ask)) => bid.checked_add(ask).map(|sum| (sum / TWO).normalize()),
+        _ => None,
+    };
+
+    // buying takes the asks and selling the bids
+    let side = match request.side() {
+        OrderSide::Buy => Side::Ask,
+        OrderSide::Sell => Side::Bid,
+    };
+
+    let (mut quantity_left, mut notional_left) = (quantity, notional);
+    let (mut filled_quantity, mut filled_notional) = (Decimal::ZERO, Decimal::ZERO);
+    let mut venues: Vec<VenueTotal> = vec![];
+    for level in merged_book
+        .get_levels(side, request.fee_adjusted)
+        .iter()
+        .filter(|level| is_requested(level.exchange))
+    {
+        if quantity_left.is_zero() && notional_left.is_zero() {
+            break;
+        }
+        let level_price = price(side, level);
+        // a notional too large to divide takes the whole level
+        let level_quantity = if notional.is_zero() {
+            level.amount.min(quantity_left)
+        } else {
+            notional_left
+                .checked_div(level_price)
+                .map_or(level.amount, |quantity| level.amount.min(quantity))
+        };
+        let level_notional = match level_quantity.checked_mul(level_price) {
+            Some(level_notional) if notional.is_zero() => level_notional,
+            Some(level_notional) => level_notional.min(notional_left),
+            None if notional.is_zero() => break,
+            None => notional_left,
+        };
+        // the walk stops at totals a decimal can't hold, leaving the estimate incomplete
+        match (
+            filled_quantity.checked_add(level_quantity),
+            filled_notional.checked_add(level_notional),
+        ) {
+            (Some(quantity), Some(notional)) => {
+                filled_quantity = quantity;
+                filled_notional = notional;
+            }
+            _ => break,
+        }
+        quantity_left = (quantity_left - level_quantity).max(Decimal::ZERO);
+        notional_left = (notional_left - level_notional).max(Decimal::ZERO);
+
+        match venues
+            .iter_mut()
+            .find(|venue| venue.exchange == level.exchange)
+        {
+            Some(venue) => {
+                venue.quantity += level_quantity;
+                venue.notional += level_notional;
+            }
+            None => venues.push(VenueTotal {
+                exchange: level.exchange,
+                quantity: level_quantity,
+                notional: level_notional,
+            }),
+        }
+    }
+
+    let average_price = get_average_price(filled_quantity, filled_notional);
+    // positive when the order trades worse than the mid
+    let slippage_bps = match (average_price, mid_price) {
+        (Some(average_price), Some(mid_price)) if !mid_price.is_zero() => {
+            let slippage = match side {
+                Side::Ask => average_price - mid_price,
+                Side::Bid => mid_price - average_price,
+            };
+            slippage
+                .checked_div(mid_price)
+                .and_then(|slippage| slippage.checked_mul(BASIS_POINTS))
+                .map(|value| value.normalize())
+        }
+        _ => None,
+    };
+
+    FillEstimate {
+        complete: !(quantity.is_zero() && notional.is_zero())
+            && quantity_left.is_zero()
+            && notional_left.is_zero(),
+        quantity: to_f64(filled_quantity),
+        notional: to_f64(filled_notional),
+        average_price: average_price.map_or(f64::NAN, to_f64),
+        mid_price: mid_price.map_or(f64::NAN, to_f64),
+        slippage_bps: slippage_bps.map_or(f64::NAN, to_f64),
+        exact_quantity: filled_quantity.normalize().to_string(),
+        exact_notional: filled_notional.normalize().to_string(),
+        exact_average_price: to_exact(average_price),
+        exact_mid_price: to_exact(mid_price),
+        exact_slippage_bps: to_exact(slippage_bps),
+        venues: venues
+            .into_iter()
+            .map(|venue| {
+                let average_price = get_average_price(venue.quantity, venue.notional);
+                VenueFill {
+                    exchange: venue.exchange.into(),
+                    quantity: to_f64(venue.quantity),
+                    notional: to_f64(venue.notional),
+                    average_price: average_price.map_or(f64::NAN, to_f64),
+                    exact_quantity: venue.quantity.normalize().to_string(),
+                    exact_notional: venue.notional.normalize().to_string(),
+                    exact_average_price: to_exact(average_price),
+                }
+            })
+            .collect(),
+        sequence: merged_book.get_sequence(),
+    }
+}
+
+fn get_average_price(quantity: Decimal, notional: Decimal) -> Option<Decimal> {
+    notional
+        .checked_div(quantity)
+        .map(|value| value.normalize())
+}
+
+fn to_exact(value: Option<Decimal>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{merged_book::TakerFees, order_book::OrderBook};
+    use rust_decimal_macros::dec;
+
+    fn get_merged_book() -> MergedBook {
+        let mut binance = OrderBook::new("binance");
+        binance.update(Side::Bid, dec!(99), dec!(1));
+        binance.update(Side::Ask, dec!(101), dec!(1));
+        binance.update(Side::Ask, dec!(103), dec!(2));
+        let mut kraken = OrderBook::new("kraken");
+        kraken.update(Side::Bid, dec!(98), dec!(2));
+        kraken.update(Side::Ask, dec!(102), dec!(1));
+        MergedBook::new([&binance, &kraken].into_iter(), vec![])
+            .with_taker_fees(&TakerFees::default().with_exchange("binance".into(), dec!(100)))
+            .with_sequence(7, None)
+    }
+
+    fn venues(estimate: &FillEstimate) -> Vec<String> {
+        estimate
+            .venues
+            .iter()
+            .map(|venue| format!("{} {}", venue.exchange, venue.exact_quantity))
+            .collect()
+    }
+
+    fn get_estimate(merged_book: &MergedBook, request: FillRequest) -> FillEstimate {
+        get_fill_estimate(merged_book, &request, get_order_size(&request).unwrap())
+    }
+
+    #[test]
+    fn test_get_fill_estimate() {
+        let merged_book = get_merged_book();
+
+        let estimate = get_estimate(
+            &merged_book,
+            FillRequest {
+                side: OrderSide::Buy.into(),
+                quantity: 3.0,
+                ..Default::default()
+            },
+        );
+        assert!(estimate.complete);
+        assert_eq!(estimate.exact_notional, "306");
+        assert_eq!(estimate.exact_average_price, "102");
+        assert_eq!(estimate.exact_mid_price, "100");
+        assert_eq!(estimate.exact_slippage_bps, "200");
+        assert_eq!(estimate.sequence, 7);
+        assert_eq!(venues(&estimate), vec!["binance 2", "kraken 1"]);
+
+        // a notional sells until the quote amount is reached
+        let estimate = get_estimate(
+            &merged_book,
+            FillRequest {
+                side: OrderSide::Sell.into(),
+                notional: 148.0,
+                ..Default::default()
+            },
+        );
+        assert!(estimate.complete);
+        assert_eq!(estimate.exact_quantity, "1.5");
+        assert_eq!(venues(&estimate), vec!["binance 1", "kraken 0.5"]);
+
+        // the fees send the order to kraken first
+        let estimate = get_estimate(
+            &merged_book,
+            FillRequest {
+                side: OrderSide::Buy.into(),
+                quantity: 1.5,
+                fee_adjusted: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(venues(&estimate), vec!["kraken 1", "binance 0.5"]);
+        assert_eq!(estimate.exact_notional, "153.005");
+
+        // the book of a single exchange can be too thin
+        let estimate = get_estimate(
+            &merged_book,
+            FillRequest {
+                side: OrderSide::Sell.into(),
+                quantity: 5.0,
+                exchanges: vec!["kraken".into()],
+                ..Default::default()
+            },
+        );
+        assert!(!estimate.complete);
+        assert_eq!(estimate.exact_quantity, "2");
+        assert_eq!(estimate.exact_slippage_bps, "200");
+    }
+
+    #[test]
+    fn test_get_fill_estimate_out_of_range() {
+        assert!(get_order_size(&FillRequest {
+            quantity: 1e40,
+            ..Default::default()
+        })
+        .is_none());
+
+        let mut order_book = OrderBook::new("binance");
+        order_book.update(Side::Ask, Decimal::MAX / dec!(2), dec!(1));
+        order_book.update(Side::Ask, Decimal::MAX, dec!(10));
+        let merged_book = MergedBook::new([&order_book].into_iter(), vec![]);
+        // the second level costs more than a decimal holds
+        let estimate = get_estimate(
+            &merged_book,
+            FillRequest {
+                side: OrderSide::Buy.into(),
+                quantity: 5.0,
+                ..Default::default()
+            },
+        );
+        assert!(!estimate.complete);
+        assert_eq!(estimate.exact_quantity, "1");
+
+        let mut order_book = OrderBook::new("binance");
+        order_book.update(Side::Ask, dec!(0.0000000000000000000000000001), dec!(2));
+        let merged_book = MergedBook::new([&order_book].into_iter(), vec![]);
+        // the notional buys more than the level has
+        let estimate = get_estimate(
+            &merged_book,
+            FillRequest {
+                side: OrderSide::Buy.into(),
+                notional: 1e20,
+                ..Default::default()
+            },
+        );
+        assert!(!estimate.complete);
+        assert_eq!(estimate.exact_quantity, "2");
+    }
+}
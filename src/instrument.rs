@@ -0,0 +1,131 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs, path::Path};
+
+// used when the server is not given an instruments file
+const BUNDLED_INSTRUMENTS: &str = include_str!("../instruments.json");
+
+// a pair with the symbol each exchange lists it under
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Instrument {
+    base: String,
+    quote: String,
+    #[serde(default)]
+    venues: BTreeMap<String, String>,
+}
+
+impl Instrument {
+    // an instrument no exchange lists, like a synthetic pair
+    pub fn new(base: &str, quote: &str) -> Self {
+        Self {
+            base: base.to_lowercase(),
+            quote: quote.to_lowercase(),
+            venues: BTreeMap::new(),
+        }
+    }
+    // like ethbtc
+    pub fn get_pair(&self) -> String {
+        format!("{}{}", self.base, self.quote)
+    }
+    pub fn get_base(&self) -> &str {
+        &self.base
+    }
+    pub fn get_quote(&self) -> &str {
+        &self.quote
+    }
+    // the symbol of the pair on an exchange, none when the exchange doesn't list it
+    pub fn get_symbol(&self, exchange: &str) -> Option<&str> {
+        self.venues.get(exchange).map(String::as_str)
+    }
+    pub fn get_venues(&self) -> Vec<&str> {
+        self.venues.keys().map(String::as_str).collect()
+    }
+}
+
+// every known instrument by its pair
+#[derive(Debug)]
+pub struct Instruments(BTreeMap<String, Instrument>);
+
+impl Default for Instruments {
+    fn default() -> Self {
+        Self::parse(BUNDLED_INSTRUMENTS).expect("the bundled instruments are valid")
+    }
+}
+
+impl Instruments {
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("can't read instruments from {}", path.display()))?;
+        Self::parse(&json).with_context(|| format!("invalid instruments in {}", path.display()))
+    }
+    pub fn parse(json: &str) -> Result<Self> {
+        let instruments: BTreeMap<String, Instrument> = serde_json::from_str(json)?;
+        let instruments = instruments
+            .into_iter()
+            .map(|(pair, mut instrument)| {
+                instrument.base = instrument.base.to_lowercase();
+                instrument.quote = instrument.quote.to_lowercase();
+                // the pair is how clients ask for the instrument, so it has to match
+                if pair.to_lowercase() != instrument.get_pair() {
+                    return Err(anyhow!(
+                        "{} is listed as {}/{}",
+                        pair,
+                        instrument.base,
+                        instrument.quote
+                    ));
+                }
+                Ok((pair.to_lowercase(), instrument))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self(instruments))
+    }
+    // a pair at least one exchange lists
+    pub fn get(&self, pair: &str) -> Result<&Instrument> {
+        let instrument = self.0.get(&pair.to_lowercase()).ok_or_else(|| {
+            anyhow!(
+                "{} is not a known pair, known pairs are {}",
+                pair,
+                self.get_pairs().join(", ")
+            )
+        })?;
+        if instrument.venues.is_empty() {
+            return Err(anyhow!("{} is not listed on any exchange", pair));
+        }
+        Ok(instrument)
+    }
+    pub fn get_pairs(&self) -> Vec<&str> {
+        self.0.keys().map(String::as_str).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instruments() {
+        let instruments = Instruments::default();
+
+        let instrument = instruments.get("ETHBTC").unwrap();
+        assert_eq!(instrument.get_pair(), "ethbtc");
+        assert_eq!(
+            instrument.get_venues(),
+            vec!["binance", "bitstamp", "coinbase", "kraken"]
+        );
+        assert_eq!(instrument.get_symbol("coinbase"), Some("ETH-BTC"));
+        assert_eq!(instrument.get_symbol("kraken"), Some("ETH/BTC"));
+
+        // binance doesn't list usd pairs
+        assert_eq!(
+            instruments.get("btcusd").unwrap().get_symbol("binance"),
+            None
+        );
+
+        let error = instruments.get("ethxyz").unwrap_err().to_string();
+        assert!(error.starts_with("ethxyz is not a known pair, known pairs are "));
+
+        assert!(Instruments::parse(r#"{"ethbtc": {"base": "btc", "quote": "eth"}}"#).is_err());
+        let instruments = Instruments::parse(r#"{"ethbtc": {"base": "eth", "quote": "btc"}}"#);
+        assert!(instruments.unwrap().get("ethbtc").is_err());
+    }
+}
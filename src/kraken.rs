@@ -0,0 +1,436 @@
+use crate::{
+    connection::Connection,
+    exchange::{Exchange, ExchangeSettings},
+    merged_book::MAX_DEPTH,
+    order_book::{OrderBook, Side},
+};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use futures_util::{SinkExt, StreamExt};
+use rust_decimal::Decimal;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use serde_json::value::RawValue;
+use std::{str::FromStr, time::Duration};
+use tokio::time;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tracing::{debug, warn};
+
+#[cfg(not(test))]
+const KRAKEN_WEB_SOCKET_URL: &str = "wss://ws.kraken.com/v2";
+
+#[cfg(test)]
+const KRAKEN_WEB_SOCKET_URL: &str = "ws://localhost:8084/ws/";
+
+const KRAKEN_DEPTH: usize = 10;
+
+// the depths the book channel accepts
+const KRAKEN_DEPTHS: [usize; 5] = [10, 25, 100, 500, 1000];
+
+// the checksum covers the ten best levels of each side
+const KRAKEN_CHECKSUM_DEPTH: usize = 10;
+
+// how long to wait for the precisions of the pair before reconnecting
+const KRAKEN_INSTRUMENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize)]
+pub struct Kraken;
+
+#[tonic::async_trait]
+impl Exchange for Kraken {
+    fn get_name() -> &'static str {
+        "kraken"
+    }
+    fn get_default_settings() -> ExchangeSettings {
+        ExchangeSettings::new(KRAKEN_WEB_SOCKET_URL).with_depth(KRAKEN_DEPTH)
+    }
+    fn validate_settings(settings: &ExchangeSettings) -> Result<()> {
+        ExchangeSettings::check("depth", settings.depth, &KRAKEN_DEPTHS)?;
+        ExchangeSettings::check::<u64>("update_speed_ms", settings.update_speed_ms, &[])
+    }
+    // https://docs.kraken.com/websockets-v2/#book
+    async fn connect(
+        symbol: String,
+        settings: ExchangeSettings,
+        connection: Connection,
+    ) -> Result<()> {
+        let depth = settings.depth.unwrap_or(KRAKEN_DEPTH);
+        let (ws_stream, _) = connect_async(&settings.websocket_url).await?;
+        let (mut write, mut read) = ws_stream.split();
+
+        // the checksum formats the levels with the precisions of the pair, which only the
+        // instrument channel gives
+        write
+            .send(Message::Text(
+                KrakenSubscription::instrument("subscribe").to_json(),
+            ))
+            .await?;
+        // a missing snapshot fails the connection so that it is retried
+        let precisions = time::timeout(KRAKEN_INSTRUMENT_TIMEOUT, async {
+            while let Some(message) = read.next().await {
+                if let Message::Text(text) = message? {
+                    connection.record_message(&text);
+                    let response: KrakenInstrumentResponse = match serde_json::from_str(&text) {
+                        Ok(response) => response,
+                        Err(_) => continue,
+                    };
+                    return response
+                        .data
+                        .pairs
+                        .into_iter()
+                        .find(|pair| pair.symbol == symbol)
+                        .map(|pair| pair.precisions)
+                        .ok_or_else(|| anyhow!("{} is not listed on kraken", symbol));
+                }
+            }
+            Err(anyhow!("no instrument snapshot received"))
+        })
+        .await
+        .map_err(|_| {
+            anyhow!(
+                "no instrument snapshot received within {:?}",
+                KRAKEN_INSTRUMENT_TIMEOUT
+            )
+        })??;
+        write
+            .send(Message::Text(
+                KrakenSubscription::instrument("unsubscribe").to_json(),
+            ))
+            .await?;
+
+        let subscribe = KrakenSubscription::new("subscribe", &symbol, depth).to_json();
+        write.send(Message::Text(subscribe.clone())).await?;
+
+        // updates are only meaningful once the snapshot has been received
+        let mut order_book: Option<OrderBook> = None;
+
+        while let Some(message) = read.next().await {
+            if let Message::Text(text) = message? {
+                connection.record_message(&text);
+                // subscription acknowledgements have no channel
+                let response_event: KrakenResponseEvent = match serde_json::from_str(&text) {
+                    Ok(response_event) => response_event,
+                    Err(_) => continue,
+                };
+                let message_type = match response_event {
+                    KrakenResponseEvent {
+                        channel: KrakenChannel::Book,
+                        message_type: Some(message_type),
+                    } => message_type,
+                    _ => continue,
+                };
+                let response: KrakenBookResponse = match serde_json::from_str(&text) {
+                    Ok(response) => response,
+                    Err(_) => {
+                        connection.record_parse_failure();
+                        continue;
+                    }
+                };
+
+                for data in response.data {
+                    let mut book = match (message_type, order_book.take()) {
+                        (KrakenMessageType::Snapshot, _) => OrderBook::new(Kraken::get_name()),
+                        (KrakenMessageType::Update, Some(book)) => book,
+                        (KrakenMessageType::Update, None) => continue,
+                    };
+                    data.apply(&mut book, depth);
+
+                    if checksum(&book, precisions) != data.checksum {
+                        // the local book can't be trusted anymore, start over from a new snapshot
+                        warn!("checksum mismatch, resubscribing");
+                        write
+                            .send(Message::Text(
+                                KrakenSubscription::new("unsubscribe", &symbol, depth).to_json(),
+                            ))
+                            .await?;
+                        write.send(Message::Text(subscribe.clone())).await?;
+                        break;
+                    }
+
+                    connection.send(book.get_top(MAX_DEPTH)).await?;
+                    debug!("order book sent");
+                    order_book = Some(book);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// https://docs.kraken.com/websockets-v2/#calculate-book-checksum
+fn checksum(order_book: &OrderBook, precisions: KrakenPrecisions) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    let asks = order_book.asks().take(KRAKEN_CHECKSUM_DEPTH);
+    let bids = order_book.bids().take(KRAKEN_CHECKSUM_DEPTH);
+    for level in asks.chain(bids) {
+        hasher.update(checksum_part(level.price, precisions.price_precision).as_bytes());
+        hasher.update(checksum_part(level.amount, precisions.qty_precision).as_bytes());
+    }
+    hasher.finalize()
+}
+
+// the decimal with the precision of the pair, without the decimal point and the leading zeros
+fn checksum_part(mut decimal: Decimal, precision: u32) -> String {
+    decimal.rescale(precision);
+    decimal
+        .to_string()
+        .replace('.', "")
+        .trim_start_matches('0')
+        .to_string()
+}
+
+#[derive(Serialize)]
+struct KrakenSubscriptionParams<'a> {
+    channel: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    symbol: Vec<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    depth: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct KrakenSubscription<'a> {
+    method: &'a str,
+    params: KrakenSubscriptionParams<'a>,
+}
+
+impl<'a> KrakenSubscription<'a> {
+    fn new(method: &'a str, symbol: &'a str, depth: usize) -> Self {
+        Self {
+            method,
+            params: KrakenSubscriptionParams {
+                channel: "book",
+                symbol: vec![symbol],
+                depth: Some(depth),
+            },
+        }
+    }
+    // every pair listed on kraken
+    fn instrument(method: &'a str) -> Self {
+        Self {
+            method,
+            params: KrakenSubscriptionParams {
+                channel: "instrument",
+                symbol: vec![],
+                depth: None,
+            },
+        }
+    }
+    fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum KrakenChannel {
+    Book,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum KrakenMessageType {
+    Snapshot,
+    Update,
+}
+
+#[derive(Deserialize)]
+struct KrakenResponseEvent {
+    channel: KrakenChannel,
+    #[serde(rename = "type")]
+    message_type: Option<KrakenMessageType>,
+}
+
+// https://docs.kraken.com/websockets-v2/#instrument
+#[derive(Deserialize)]
+struct KrakenInstrumentResponse {
+    data: KrakenInstrumentData,
+}
+
+#[derive(Deserialize)]
+struct KrakenInstrumentData {
+    pairs: Vec<KrakenPair>,
+}
+
+#[derive(Deserialize)]
+struct KrakenPair {
+    symbol: String,
+    #[serde(flatten)]
+    precisions: KrakenPrecisions,
+}
+
+// the decimals of the prices and quantities of a pair
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+struct KrakenPrecisions {
+    price_precision: u32,
+    qty_precision: u32,
+}
+
+#[derive(Deserialize)]
+struct KrakenBookResponse {
+    data: Vec<KrakenBookData>,
+}
+
+#[derive(Deserialize)]
+struct KrakenBookData {
+    bids: Vec<KrakenLevel>,
+    asks: Vec<KrakenLevel>,
+    checksum: u32,
+    // only sent with updates
+    timestamp: Option<DateTime<Utc>>,
+}
+
+impl KrakenBookData {
+    fn apply(&self, order_book: &mut OrderBook, depth: usize) {
+        for bid in &self.bids {
+            order_book.update(Side::Bid, bid.price, bid.qty);
+        }
+        for ask in &self.asks {
+            order_book.update(Side::Ask, ask.price, ask.qty);
+        }
+        // levels pushed out of the subscribed depth are not updated anymore
+        order_book.truncate(depth);
+        if let Some(timestamp) = self.timestamp {
+            order_book.set_event_time(timestamp.into());
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct KrakenLevel {
+    #[serde(deserialize_with = "deserialize_decimal")]
+    price: Decimal,
+    #[serde(deserialize_with = "deserialize_decimal")]
+    qty: Decimal,
+}
+
+// prices are JSON numbers, read them from the raw text so they don't go through a float
+fn deserialize_decimal<'de, D>(deserializer: D) -> Result<Decimal, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let raw: Box<RawValue> = Deserialize::deserialize(deserializer)?;
+    Decimal::from_str(raw.get()).map_err(de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        exchange::get_order_book,
+        test_data::{
+            get_kraken_bad_checksum_update_response, get_kraken_instrument_response,
+            get_kraken_short_numbers_snapshot_response, get_kraken_snapshot_response,
+            get_kraken_update_response,
+        },
+        test_server::TestServer,
+    };
+    use tokio::spawn;
+
+    #[test]
+    fn test_checksum() {
+        let response: KrakenInstrumentResponse =
+            serde_json::from_str(get_kraken_instrument_response()).unwrap();
+        let precisions = response.data.pairs[0].precisions;
+        assert_eq!(
+            precisions,
+            KrakenPrecisions {
+                price_precision: 5,
+                qty_precision: 8
+            }
+        );
+
+        // the checksum doesn't depend on how the numbers were written
+        for snapshot in [
+            get_kraken_snapshot_response(),
+            get_kraken_short_numbers_snapshot_response(),
+        ] {
+            let response: KrakenBookResponse = serde_json::from_str(snapshot).unwrap();
+            let mut order_book = OrderBook::new(Kraken::get_name());
+            response.data[0].apply(&mut order_book, KRAKEN_DEPTH);
+            assert_eq!(checksum(&order_book, precisions), response.data[0].checksum);
+        }
+        assert_eq!(
+            checksum_part(Decimal::from_str("0.1").unwrap(), 8),
+            "10000000"
+        );
+        assert_eq!(
+            checksum_part(Decimal::from_str("45281.0").unwrap(), 1),
+            "452810"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_kraken_websocket() {
+        let mut server = TestServer::new("8084").await;
+        let (connection, mut receiver) = server.get_connection::<Kraken>();
+
+        spawn(get_order_book::<Kraken>(
+            "ETH/BTC".into(),
+            Kraken::get_default_settings(),
+            connection,
+        ));
+
+        // the precisions of the pair come first
+        assert_eq!(
+            server.receive_message().await.unwrap(),
+            "{\"method\":\"subscribe\",\"params\":{\"channel\":\"instrument\"}}"
+        );
+        server.send_message(get_kraken_instrument_response()).await;
+        let unsubscription = server.receive_message().await.unwrap();
+        assert!(unsubscription.starts_with("{\"method\":\"unsubscribe\""));
+
+        let subscription = server.receive_message().await.unwrap();
+        assert_eq!(
+            subscription,
+            "{\"method\":\"subscribe\",\"params\":{\"channel\":\"book\",\"symbol\":[\"ETH/BTC\"],\"depth\":10}}"
+        );
+
+        server.send_message(get_kraken_snapshot_response()).await;
+
+        let order_book = receiver.recv().await.unwrap();
+        assert_eq!(order_book.get_exchange_name(), "kraken");
+        let (bids, asks) = order_book.get_levels(10);
+        assert_eq!(bids.len(), 3);
+        assert_eq!(asks.len(), 3);
+        assert_eq!(bids[0].price, 0.06791);
+        assert_eq!(asks[0].price, 0.06792);
+
+        server.send_message(get_kraken_update_response()).await;
+
+        let order_book = receiver.recv().await.unwrap();
+        let (bids, asks) = order_book.get_levels(10);
+        assert_eq!(bids.len(), 2);
+        assert_eq!(bids[0].price, 0.0679);
+        assert_eq!(asks[0].amount, 2.5);
+        assert_eq!(
+            order_book.get_event_time(),
+            Some(
+                "2023-04-22T12:41:26.795358Z"
+                    .parse::<DateTime<Utc>>()
+                    .unwrap()
+                    .into()
+            )
+        );
+
+        // a bad checksum drops the book and resubscribes
+        server
+            .send_message(get_kraken_bad_checksum_update_response())
+            .await;
+
+        let unsubscription = server.receive_message().await.unwrap();
+        assert!(unsubscription.starts_with("{\"method\":\"unsubscribe\""));
+        assert_eq!(server.receive_message().await.unwrap(), subscription);
+
+        // updates are ignored until the new snapshot
+        server.send_message(get_kraken_update_response()).await;
+        server.send_message(get_kraken_snapshot_response()).await;
+
+        let (bids, asks) = receiver.recv().await.unwrap().get_levels(10);
+        assert_eq!(bids.len(), 3);
+        assert_eq!(asks.len(), 3);
+        assert_eq!(asks[0].amount, 1.1);
+    }
+}
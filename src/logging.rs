@@ -0,0 +1,48 @@
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::io::{self, IsTerminal};
+use tracing_subscriber::{fmt, EnvFilter};
+
+pub const DEFAULT_LOG_LEVEL: &str = "info";
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    // one line per event, with the fields of its spans
+    #[default]
+    Human,
+    // one json object per event, for log collectors
+    Json,
+}
+
+// the level is a filter like info or warn,server=debug
+pub fn get_filter(level: &str) -> Result<EnvFilter> {
+    EnvFilter::try_new(level).with_context(|| format!("invalid log level {}", level))
+}
+
+// logs to stderr, the events are dropped until it is called
+pub fn init(format: LogFormat, level: &str) -> Result<()> {
+    let builder = fmt()
+        .with_env_filter(get_filter(level)?)
+        .with_writer(io::stderr)
+        // colors only make sense in a terminal
+        .with_ansi(io::stderr().is_terminal());
+    match format {
+        LogFormat::Human => builder.try_init(),
+        LogFormat::Json => builder.json().flatten_event(true).try_init(),
+    }
+    .map_err(|error| anyhow!(error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_filter() {
+        assert!(get_filter("debug").is_ok());
+        assert!(get_filter("warn,server::connection=trace").is_ok());
+        assert!(get_filter("server=loud").is_err());
+    }
+}
@@ -1,47 +1,333 @@
+mod aggregator;
+mod arbitrage;
 mod binance;
 mod bitstamp;
+mod coinbase;
+mod config;
+mod connection;
+mod delta;
 mod exchange;
+mod export;
+mod fill;
+mod instrument;
+mod kraken;
+mod logging;
+mod merged_book;
+mod metrics;
+mod microstructure;
 mod order_book;
 mod proto;
+mod recorder;
+mod replay;
 mod service;
-use crate::{proto::OrderbookAggregatorServer, service::OrderBookService};
-use anyhow::Result;
+mod synthetic;
+use crate::{
+    binance::BinanceStream,
+    config::Config,
+    export::Export,
+    instrument::Instruments,
+    logging::LogFormat,
+    proto::OrderbookAggregatorServer,
+    recorder::Recorder,
+    replay::{Replay, ReplaySpeed},
+    service::OrderBookService,
+    synthetic::SyntheticPair,
+};
+use anyhow::{anyhow, Result};
 use clap::Parser;
-use std::error::Error;
+use rust_decimal::Decimal;
+use std::{env, error::Error, future::pending, net::SocketAddr, path::PathBuf, str::FromStr};
+use tokio::{
+    select,
+    signal::{
+        ctrl_c,
+        unix::{signal, SignalKind},
+    },
+};
 use tonic::transport::Server;
+use tracing::{info, warn};
 #[cfg(test)]
 mod test_data;
 
 #[cfg(test)]
 mod test_server;
 
+// the flags override the config file, which the ORDERBOOK_ environment variables override
 #[derive(Parser)]
 struct Args {
+    /// TOML file of the server settings
     #[arg(short, long)]
-    pair: String,
+    config: Option<PathBuf>,
+    /// Address the gRPC server listens on, [::1]:10000 when unset
+    #[arg(long)]
+    listen_address: Option<SocketAddr>,
+    /// Address serving the Prometheus metrics on /metrics, disabled when unset
+    #[arg(long)]
+    metrics_address: Option<SocketAddr>,
+    /// Format of the logs written to stderr, human when unset
+    #[arg(long, value_enum)]
+    log_format: Option<LogFormat>,
+    /// Level of the logs like debug, or a filter like warn,server=debug
+    #[arg(long)]
+    log_level: Option<String>,
+    /// Pair to aggregate, repeat it to aggregate several pairs
+    #[arg(short, long)]
+    pair: Vec<String>,
+    /// JSON file of the pairs and their symbol on each exchange, the bundled one when unset
+    #[arg(long)]
+    instruments: Option<PathBuf>,
+    /// Print the exchanges listing each pair, or every known pair, and exit
+    #[arg(long)]
+    list_venues: bool,
+    #[arg(long, value_enum)]
+    binance_stream: Option<BinanceStream>,
+    /// Milliseconds after which an exchange without updates is left out of the summary, 10000 when unset
+    #[arg(long)]
+    stale_after_ms: Option<u64>,
+    /// Staleness timeout of a single exchange, like binance=2000
+    #[arg(long, value_parser = parse_exchange_setting::<u64>)]
+    exchange_stale_after_ms: Vec<(String, u64)>,
+    /// Taker fee of an exchange in basis points, like binance=7.5
+    #[arg(long, value_parser = parse_exchange_setting::<Decimal>)]
+    taker_fee_bps: Vec<(String, Decimal)>,
+    /// Pair implied from two aggregated pairs, like etheur=ethbtc,btceur
+    #[arg(long)]
+    synthetic: Vec<String>,
+    /// Directory to record the raw Binance and Bitstamp frames to
+    #[arg(long, value_name = "DIR")]
+    record: Option<PathBuf>,
+    /// Directory to write the summaries of every pair to, as CSV and Parquet files
+    #[arg(long, value_name = "DIR")]
+    export: Option<PathBuf>,
+    /// Capture file, or directory of them, to play instead of connecting the exchanges
+    #[arg(long, value_name = "FILE")]
+    replay: Option<PathBuf>,
+    /// Speed of the replay, like 1 for the original speed, 10, or max
+    #[arg(long, value_name = "SPEED", default_value_t = ReplaySpeed::default())]
+    replay_speed: ReplaySpeed,
 }
 
-async fn start_server(pair: String) -> Result<()> {
-    let addresse = "[::1]:10000".parse().unwrap();
+fn parse_exchange_setting<T>(value: &str) -> Result<(String, T)>
+where
+    T: FromStr,
+    T::Err: Error + Send + Sync + 'static,
+{
+    let (exchange, setting) = value
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected <exchange>=<value>, got {}", value))?;
+    Ok((exchange.to_lowercase(), setting.parse()?))
+}
 
-    println!("OrderbookAggregatorServer listening on: {}", addresse);
+fn load_config(args: &Args) -> Result<Config> {
+    let mut config = Config::load(args.config.as_deref(), env::vars())?;
+    if let Some(listen_address) = args.listen_address {
+        config.listen_address = listen_address;
+    }
+    if let Some(metrics_address) = args.metrics_address {
+        config.metrics_address = Some(metrics_address);
+    }
+    if let Some(log_format) = args.log_format {
+        config.log_format = log_format;
+    }
+    if let Some(log_level) = &args.log_level {
+        config.log_level = log_level.clone();
+    }
+    if !args.pair.is_empty() {
+        config.pairs = args.pair.clone();
+    }
+    if !args.synthetic.is_empty() {
+        config.synthetic = args.synthetic.clone();
+    }
+    if let Some(instruments) = &args.instruments {
+        config.instruments = Some(instruments.clone());
+    }
+    if let Some(binance_stream) = args.binance_stream {
+        config.binance_stream = binance_stream;
+    }
+    if let Some(stale_after_ms) = args.stale_after_ms {
+        config.stale_after_ms = stale_after_ms;
+    }
+    for (exchange, timeout) in &args.exchange_stale_after_ms {
+        config
+            .exchanges
+            .entry(exchange.clone())
+            .or_default()
+            .stale_after_ms = Some(*timeout);
+    }
+    for (exchange, fee_bps) in &args.taker_fee_bps {
+        config
+            .exchanges
+            .entry(exchange.clone())
+            .or_default()
+            .taker_fee_bps = Some(*fee_bps);
+    }
+    if let Some(directory) = &args.record {
+        config
+            .recorder
+            .get_or_insert_with(Default::default)
+            .directory = directory.clone();
+    }
+    if let Some(directory) = &args.export {
+        config.export.get_or_insert_with(Default::default).directory = directory.clone();
+    }
+    Ok(config)
+}
+
+fn load_instruments(config: &Config) -> Result<Instruments> {
+    match &config.instruments {
+        Some(path) => Instruments::load(path),
+        None => Ok(Instruments::default()),
+    }
+}
+
+fn list_venues(args: &Args) -> Result<()> {
+    let config = load_config(args)?;
+    let instruments = load_instruments(&config)?;
+    let pairs = match config.pairs.is_empty() {
+        true => instruments
+            .get_pairs()
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        false => config.pairs,
+    };
+    for pair in pairs {
+        let instrument = instruments.get(&pair)?;
+        println!(
+            "{}: {}",
+            instrument.get_pair(),
+            instrument.get_venues().join(", ")
+        );
+    }
+    Ok(())
+}
 
-    let order_book_service = OrderBookService::new(pair).connect_exchanges();
+async fn start_server(args: Args) -> Result<()> {
+    let config = load_config(&args)?;
+    config.validate()?;
+    logging::init(config.log_format, &config.log_level)?;
+
+    // an unknown pair would only show up as exchanges never sending anything
+    let instruments = load_instruments(&config)?;
+    let pairs = config
+        .pairs
+        .iter()
+        .map(|pair| instruments.get(pair).cloned())
+        .collect::<Result<Vec<_>>>()?;
+    let synthetic_pairs = config
+        .synthetic
+        .iter()
+        .map(|synthetic| SyntheticPair::parse(synthetic, &instruments))
+        .collect::<Result<Vec<_>>>()?;
+    for instrument in &pairs {
+        info!(
+            pair = %instrument.get_pair(),
+            venues = %instrument.get_venues().join(", "),
+            "pair configured"
+        );
+    }
+
+    if let Some(metrics_address) = config.metrics_address {
+        metrics::install(metrics_address)?;
+        info!(%metrics_address, "serving metrics on /metrics");
+    }
+
+    let replay = match &args.replay {
+        Some(path) => {
+            let replay = Replay::new(path)?.with_speed(args.replay_speed);
+            info!(
+                path = %replay.get_path().display(),
+                speed = %replay.get_speed(),
+                "replaying the captures, a pair starts with its first subscriber"
+            );
+            Some(replay)
+        }
+        None => None,
+    };
+
+    // there is nothing to record while replaying
+    let recorder = match config.recorder.clone().filter(|_| replay.is_none()) {
+        Some(settings) => {
+            info!(directory = %settings.directory.display(), "recording binance and bitstamp frames");
+            Some(Recorder::new(settings)?)
+        }
+        None => None,
+    };
+
+    let addresse = config.listen_address;
+
+    info!(address = %addresse, "OrderbookAggregatorServer listening");
+
+    let order_book_service = config
+        .get_exchange_settings()?
+        .into_iter()
+        .fold(
+            OrderBookService::new(pairs)
+                .with_binance_stream(config.binance_stream)
+                .with_staleness_timeouts(config.get_staleness_timeouts())
+                .with_taker_fees(config.get_taker_fees())
+                .with_channel_buffer_size(config.channel_buffer_size)
+                .with_recorder(recorder.clone())
+                .with_replay(replay)
+                .with_synthetic_pairs(synthetic_pairs),
+            |service, (exchange, settings)| service.with_exchange_settings(exchange, settings),
+        )
+        .connect_exchanges();
+
+    let export = match config.export.clone() {
+        Some(settings) => {
+            info!(directory = %settings.directory.display(), "exporting the summaries");
+            let summaries = order_book_service.subscribe_summaries(&settings.get_request());
+            Some(Export::start(settings, summaries)?)
+        }
+        None => None,
+    };
 
     let order_book_server = OrderbookAggregatorServer::new(order_book_service);
 
     Server::builder()
         .add_service(order_book_server)
-        .serve(addresse)
+        .serve_with_shutdown(addresse, wait_for_shutdown())
         .await?;
 
+    if let Some(export) = export {
+        export.stop().await;
+    }
+    if let Some(recorder) = recorder {
+        recorder.stop().await;
+    }
+
     Ok(())
 }
 
+// ctrl-c, or the termination signal of service managers and containers
+async fn wait_for_shutdown() {
+    let terminate = async {
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(error) => {
+                warn!(%error, "can't listen to the termination signal");
+                pending::<()>().await;
+            }
+        }
+    };
+    select! {
+        _ = ctrl_c() => {}
+        _ = terminate => {}
+    }
+    info!("shutting down");
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
-    start_server(args.pair).await?;
+    if args.list_venues {
+        list_venues(&args)?;
+        return Ok(());
+    }
+    start_server(args).await?;
 
     Ok(())
 }
@@ -50,7 +336,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
 mod tests {
     use super::*;
     use crate::{
-        proto::{Empty, OrderbookAggregatorClient, Summary},
+        proto::{ConnectionState, Empty, OrderbookAggregatorClient, Summary, SummaryRequest},
         test_data::{get_binance_websocket_response, get_bitstamp_websocket_response},
         test_server::TestServer,
     };
@@ -59,12 +345,15 @@ mod tests {
         spawn,
         time::{sleep, Duration},
     };
-    use tonic::Streaming;
+    use tonic::{Code, Streaming};
 
     async fn start_client() -> Result<Streaming<Summary>> {
         let mut client = OrderbookAggregatorClient::connect("http://[::1]:10000").await?;
 
-        let stream = client.book_summary(Empty {}).await?.into_inner();
+        let stream = client
+            .book_summary(SummaryRequest::default())
+            .await?
+            .into_inner();
 
         Ok(stream)
     }
@@ -74,7 +363,9 @@ mod tests {
         let mut binance_server = TestServer::new("8080").await;
         let mut bitstamp_server = TestServer::new("8081").await;
 
-        spawn(start_server("ethbtc".into()));
+        spawn(start_server(Args::parse_from([
+            "server", "--pair", "ethbtc",
+        ])));
         // wait for server to start (kinda hacky but works for now)
         sleep(Duration::from_millis(1000)).await;
 
@@ -87,7 +378,15 @@ mod tests {
 
         // Then we wait for a summary from the stream and assert the data
         if let Some(summary) = stream.message().await? {
-            let Summary { bids, asks, .. } = summary;
+            let Summary {
+                bids,
+                asks,
+                sequence,
+                trigger_exchange,
+                ..
+            } = summary;
+            assert_eq!(sequence, 1);
+            assert_eq!(trigger_exchange, "binance");
             assert_eq!(bids.len(), 10);
             assert_eq!(asks.len(), 10);
 
@@ -107,7 +406,18 @@ mod tests {
 
         // Then we wait for a summary from the stream and assert the data
         if let Some(summary) = stream.message().await? {
-            let Summary { bids, asks, .. } = summary;
+            let Summary {
+                bids,
+                asks,
+                sequence,
+                trigger_exchange,
+                exchange_times,
+                ..
+            } = summary;
+            assert_eq!(sequence, 2);
+            assert_eq!(trigger_exchange, "bitstamp");
+            assert_eq!(exchange_times.len(), 2);
+            assert!(exchange_times[1].event_time_us > 0);
             assert_eq!(bids.len(), 10);
             assert_eq!(asks.len(), 10);
 
@@ -120,6 +430,68 @@ mod tests {
             assert_eq!(asks[9].price, 0.06795205);
         }
 
+        let mut client = OrderbookAggregatorClient::connect("http://[::1]:10000").await?;
+
+        // the current summary is available right away
+        let summary = client
+            .get_summary(SummaryRequest::default())
+            .await?
+            .into_inner();
+        assert_eq!(summary.asks[0].price, 0.06792853);
+
+        // and a new subscriber starts with it without waiting for an update
+        let mut second_stream = start_client().await?;
+        assert_eq!(second_stream.message().await?, Some(summary));
+        drop(second_stream);
+
+        // both exchanges delivered data, the others have nothing listening
+        let connections = client
+            .get_exchange_connections(Empty {})
+            .await?
+            .into_inner()
+            .connections;
+        let state = |exchange: &str| {
+            connections
+                .iter()
+                .find(|connection| connection.exchange == exchange)
+                .map(|connection| connection.state())
+        };
+        assert_eq!(state("binance"), Some(ConnectionState::Connected));
+        assert_eq!(state("bitstamp"), Some(ConnectionState::Connected));
+        assert_ne!(state("kraken"), Some(ConnectionState::Connected));
+        assert!(connections
+            .iter()
+            .all(|connection| connection.symbol == "ethbtc"));
+
+        // only configured pairs can be subscribed to
+        let status = client
+            .book_summary(SummaryRequest {
+                symbol: "btcusdt".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+
+        let status = client
+            .get_summary(SummaryRequest {
+                vwap_notionals: vec![-1.0],
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        // the exchanges are disconnected once the last subscriber leaves
+        drop(stream);
+        sleep(Duration::from_millis(100)).await;
+        let connections = client
+            .get_exchange_connections(Empty {})
+            .await?
+            .into_inner()
+            .connections;
+        assert!(connections.is_empty());
+
         Ok(())
     }
 }
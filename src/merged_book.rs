@@ -0,0 +1,524 @@
+use crate::{
+    microstructure::get_microstructure,
+    order_book::{to_f64, OrderBook, PriceLevel, Side},
+    proto::{ExchangeTimes, Level, Summary, SummaryRequest, VenueAmount},
+};
+use rust_decimal::Decimal;
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// deepest book a subscriber can ask for
+pub const MAX_DEPTH: usize = 100;
+const DEFAULT_DEPTH: usize = 10;
+pub const BASIS_POINTS: Decimal = Decimal::from_parts(10000, 0, 0, false, 0);
+
+// taker fee of each exchange in basis points, what crossing the spread there really costs
+#[derive(Clone, Debug, Default)]
+pub struct TakerFees(HashMap<String, Decimal>);
+
+impl TakerFees {
+    pub fn with_exchange(mut self, exchange: String, fee_bps: Decimal) -> Self {
+        self.0.insert(exchange, fee_bps);
+        self
+    }
+    // a bid is worth less and an ask costs more once the fee is paid
+    pub fn get_adjusted_price(&self, side: Side, level: &PriceLevel) -> Decimal {
+        let rate = self.0.get(level.exchange).copied().unwrap_or_default() / BASIS_POINTS;
+        let adjusted_price = match side {
+            Side::Bid => level.price * (Decimal::ONE - rate),
+            Side::Ask => level.price * (Decimal::ONE + rate),
+        };
+        adjusted_price.normalize()
+    }
+}
+
+// when an exchange produced its book and when it was received
+#[derive(Clone, Copy, Debug)]
+pub struct BookTimes {
+    exchange: &'static str,
+    event_time: Option<SystemTime>,
+    received_time: SystemTime,
+}
+
+impl From<&OrderBook> for BookTimes {
+    fn from(order_book: &OrderBook) -> Self {
+        Self {
+            exchange: order_book.get_exchange_name(),
+            event_time: order_book.get_event_time(),
+            received_time: order_book.get_received_time(),
+        }
+    }
+}
+
+impl From<BookTimes> for ExchangeTimes {
+    fn from(book_times: BookTimes) -> Self {
+        Self {
+            exchange: book_times.exchange.into(),
+            event_time_us: book_times.event_time.map_or(0, to_unix_micros),
+            received_at_us: to_unix_micros(book_times.received_time),
+        }
+    }
+}
+
+fn to_unix_micros(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_micros() as u64)
+}
+
+// the books of all the fresh exchanges merged together, shared by every subscriber
+#[derive(Debug)]
+pub struct MergedBook {
+    bids: Vec<PriceLevel>,
+    asks: Vec<PriceLevel>,
+    // the same levels ordered by their fee adjusted price
+    adjusted_bids: Vec<PriceLevel>,
+    adjusted_asks: Vec<PriceLevel>,
+    taker_fees: TakerFees,
+    stale_exchanges: Vec<String>,
+    sequence: u64,
+    built_time: SystemTime,
+    trigger_exchange: Option<&'static str>,
+    book_times: Vec<BookTimes>,
+}
+
+impl Default for MergedBook {
+    fn default() -> Self {
+        MergedBook::new([].into_iter(), vec![])
+    }
+}
+
+impl MergedBook {
+    pub fn new<'a>(
+        order_books: impl Iterator<Item = &'a OrderBook> + Clone,
+        mut stale_exchanges: Vec<String>,
+    ) -> Self {
+        // the best levels of a single exchange are enough to serve any requested depth
+        let mut bids: Vec<PriceLevel> = order_books
+            .clone()
+            .flat_map(|order_book| order_book.bids().take(MAX_DEPTH))
+            .collect();
+        let mut asks: Vec<PriceLevel> = order_books
+            .flat_map(|order_book| order_book.asks().take(MAX_DEPTH))
+            .collect();
+
+        bids.sort_by(|a, b| b.cmp(a));
+        asks.sort();
+        stale_exchanges.sort();
+
+        Self {
+            adjusted_bids: bids.clone(),
+            adjusted_asks: asks.clone(),
+            bids,
+            asks,
+            taker_fees: TakerFees::default(),
+            stale_exchanges,
+            sequence: 0,
+            built_time: SystemTime::now(),
+            trigger_exchange: None,
+            book_times: vec![],
+        }
+    }
+    pub fn with_taker_fees(self, taker_fees: &TakerFees) -> Self {
+        let mut adjusted_bids = self.bids.clone();
+        let mut adjusted_asks = self.asks.clone();
+        // stable sorts, so equal adjusted prices keep the raw order
+        adjusted_bids.sort_by_key(|level| {
+            std::cmp::Reverse(taker_fees.get_adjusted_price(Side::Bid, level))
+        });
+        adjusted_asks.sort_by_key(|level| taker_fees.get_adjusted_price(Side::Ask, level));
+        Self {
+            adjusted_bids,
+            adjusted_asks,
+            taker_fees: taker_fees.clone(),
+            ..self
+        }
+    }
+    pub fn with_sequence(self, sequence: u64, trigger_exchange: Option<&'static str>) -> Self {
+        Self {
+            sequence,
+            trigger_exchange,
+            ..self
+        }
+    }
+    pub fn with_book_times(self, mut book_times: Vec<BookTimes>) -> Self {
+        book_times.sort_by_key(|book_times| book_times.exchange);
+        Self { book_times, ..self }
+    }
+    pub fn get_stale_exchanges(&self) -> &[String] {
+        &self.stale_exchanges
+    }
+    // the levels of a side, ordered by their raw or fee adjusted price
+    pub fn get_levels(&self, side: Side, fee_adjusted: bool) -> &[PriceLevel] {
+        match (side, fee_adjusted) {
+            (Side::Bid, false) => &self.bids,
+            (Side::Ask, false) => &self.asks,
+            (Side::Bid, true) => &self.adjusted_bids,
+            (Side::Ask, true) => &self.adjusted_asks,
+        }
+    }
+    pub fn get_taker_fees(&self) -> &TakerFees {
+        &self.taker_fees
+    }
+    pub fn get_sequence(&self) -> u64 {
+        self.sequence
+    }
+    // best ask minus best bid before fees, none when a side is missing
+    pub fn get_spread(&self) -> Option<Decimal> {
+        Some(self.asks.first()?.price - self.bids.first()?.price)
+    }
+    pub fn get_summary(&self, request: &SummaryRequest) -> Summary {
+        let depth = match request.depth as usize {
+            0 => DEFAULT_DEPTH,
+            depth => depth.min(MAX_DEPTH),
+        };
+        let is_requested = |exchange: &str| {
+            request.exchanges.is_empty()
+                || request
+                    .exchanges
+                    .iter()
+                    .any(|requested| requested.eq_ignore_ascii_case(exchange))
+        };
+
+        let bids = self
+            .get_levels(Side::Bid, request.fee_adjusted)
+            .iter()
+            .filter(|level| is_requested(level.exchange));
+        let asks = self
+            .get_levels(Side::Ask, request.fee_adjusted)
+            .iter()
+            .filter(|level| is_requested(level.exchange));
+
+        let price = |side: Side, level: &PriceLevel| {
+            if request.fee_adjusted {
+                self.taker_fees.get_adjusted_price(side, level)
+            } else {
+                level.price
+            }
+        };
+        let spread = match (asks.clone().next(), bids.clone().next()) {
+            (Some(first_ask), Some(first_bid)) => {
+                Some(price(Side::Ask, first_ask) - price(Side::Bid, first_bid))
+            }
+            _ => None,
+        };
+
+        // the metrics see every level of the requested exchanges, not only the requested depth
+        let microstructure = get_microstructure(
+            &bids
+                .clone()
+                .map(|level| (price(Side::Bid, level), level.amount))
+                .collect::<Vec<_>>(),
+            &asks
+                .clone()
+                .map(|level| (price(Side::Ask, level), level.amount))
+                .collect::<Vec<_>>(),
+            request,
+        );
+
+        let (bids, asks) = if request.consolidated {
+            (
+                self.consolidate(Side::Bid, bids, depth, request.fee_adjusted),
+                self.consolidate(Side::Ask, asks, depth, request.fee_adjusted),
+            )
+        } else {
+            (
+                self.to_levels(Side::Bid, bids, depth),
+                self.to_levels(Side::Ask, asks, depth),
+            )
+        };
+
+        Summary {
+            spread: spread.map_or(f64::NAN, to_f64),
+            exact_spread: spread.map(|spread| spread.to_string()).unwrap_or_default(),
+            bids,
+            asks,
+            stale_exchanges: self.stale_exchanges.clone(),
+            sequence: self.sequence,
+            built_at_us: to_unix_micros(self.built_time),
+            trigger_exchange: self.trigger_exchange.unwrap_or_default().into(),
+            exchange_times: self
+                .book_times
+                .iter()
+                .filter(|book_times| is_requested(book_times.exchange))
+                .map(|book_times| ExchangeTimes::from(*book_times))
+                .collect(),
+            ..microstructure
+        }
+    }
+    fn to_level(&self, side: Side, level: &PriceLevel) -> Level {
+        let adjusted_price = self.taker_fees.get_adjusted_price(side, level);
+        Level {
+            adjusted_price: to_f64(adjusted_price),
+            exact_adjusted_price: adjusted_price.to_string(),
+            ..Level::from(*level)
+        }
+    }
+    fn to_levels<'a>(
+        &self,
+        side: Side,
+        levels: impl Iterator<Item = &'a PriceLevel>,
+        depth: usize,
+    ) -> Vec<Level> {
+        levels
+            .take(depth)
+            .map(|level| self.to_level(side, level))
+            .collect()
+    }
+    // one level per raw price, or per adjusted price once the fees are paid, with the amount of
+    // each exchange
+    fn consolidate<'a>(
+        &self,
+        side: Side,
+        levels: impl Iterator<Item = &'a PriceLevel>,
+        depth: usize,
+        fee_adjusted: bool,
+    ) -> Vec<Level> {
+        let mut prices: BTreeMap<Decimal, Vec<&PriceLevel>> = BTreeMap::new();
+        for level in levels {
+            let price = match fee_adjusted {
+                true => self.taker_fees.get_adjusted_price(side, level),
+                false => level.price,
+            };
+            prices.entry(price).or_default().push(level);
+        }
+        let mut prices: Vec<(Decimal, Vec<&PriceLevel>)> = prices.into_iter().collect();
+        // highest bid first
+        if side == Side::Bid {
+            prices.reverse();
+        }
+
+        prices
+            .into_iter()
+            .take(depth)
+            .map(|(price, venues)| {
+                let amount: Decimal = venues.iter().map(|venue| venue.amount).sum();
+                let level = self.to_level(side, venues[0]);
+                // the venues of a fee adjusted level only share their adjusted price
+                let level = match fee_adjusted {
+                    true => Level {
+                        price: to_f64(price),
+                        exact_price: price.to_string(),
+                        adjusted_price: to_f64(price),
+                        exact_adjusted_price: price.to_string(),
+                        ..level
+                    },
+                    false => level,
+                };
+                Level {
+                    exchange: String::new(),
+                    amount: to_f64(amount),
+                    exact_amount: amount.to_string(),
+                    venues: venues
+                        .iter()
+                        .map(|venue| VenueAmount {
+                            exchange: venue.exchange.into(),
+                            amount: to_f64(venue.amount),
+                            exact_amount: venue.amount.to_string(),
+                        })
+                        .collect(),
+                    ..level
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_data::{get_binance_order_book_builder, get_bitstamp_order_book_builder};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_get_summary() {
+        let binance_order_book = get_binance_order_book_builder().build();
+        let bitstamp_order_book = get_bitstamp_order_book_builder().build();
+        let merged_book = MergedBook::new(
+            [&binance_order_book, &bitstamp_order_book].into_iter(),
+            vec![],
+        )
+        .with_sequence(42, Some("bitstamp"))
+        .with_book_times(vec![
+            BookTimes::from(&bitstamp_order_book),
+            BookTimes::from(&binance_order_book),
+        ]);
+
+        // 10 levels of every exchange by default
+        let summary = merged_book.get_summary(&SummaryRequest::default());
+        assert_eq!(summary.bids.len(), 10);
+        assert_eq!(summary.asks.len(), 10);
+        assert_eq!(summary.exact_spread, "0.000001");
+        assert_eq!(summary.sequence, 42);
+        assert_eq!(summary.trigger_exchange, "bitstamp");
+        assert!(summary.built_at_us > 0);
+        let exchanges: Vec<&str> = summary
+            .exchange_times
+            .iter()
+            .map(|times| times.exchange.as_str())
+            .collect();
+        assert_eq!(exchanges, vec!["binance", "bitstamp"]);
+        assert_eq!(summary.exchange_times[0].event_time_us, 0);
+        assert!(summary.exchange_times[0].received_at_us > 0);
+
+        // deeper than a single exchange
+        let summary = merged_book.get_summary(&SummaryRequest {
+            depth: 25,
+            ..Default::default()
+        });
+        assert_eq!(summary.bids.len(), 25);
+        assert_eq!(summary.asks.len(), 25);
+
+        // capped to the max depth
+        let summary = merged_book.get_summary(&SummaryRequest {
+            depth: 1000,
+            ..Default::default()
+        });
+        assert_eq!(summary.bids.len(), MAX_DEPTH);
+
+        // only bitstamp, with its own spread
+        let summary = merged_book.get_summary(&SummaryRequest {
+            depth: 5,
+            exchanges: vec!["Bitstamp".into()],
+            ..Default::default()
+        });
+        assert_eq!(summary.bids.len(), 5);
+        assert!(summary.bids.iter().all(|bid| bid.exchange == "bitstamp"));
+        assert!(summary.asks.iter().all(|ask| ask.exchange == "bitstamp"));
+        assert_eq!(summary.bids[0].price, 0.06842268);
+        assert_eq!(summary.asks[0].price, 0.06843007);
+        assert_eq!(summary.exact_spread, "0.00000739");
+        assert_eq!(summary.exchange_times.len(), 1);
+
+        // one level per price with the amount of each exchange
+        let summary = merged_book.get_summary(&SummaryRequest {
+            consolidated: true,
+            ..Default::default()
+        });
+        assert_eq!(summary.bids.len(), 10);
+        assert!(summary
+            .bids
+            .windows(2)
+            .all(|levels| levels[0].price > levels[1].price));
+        assert_eq!(summary.exact_spread, "0.000001");
+        let shared = summary
+            .bids
+            .iter()
+            .find(|level| level.venues.len() > 1)
+            .unwrap();
+        assert_eq!(shared.exact_price, "0.06842268");
+        assert!(shared.exchange.is_empty());
+        let total: Decimal = shared
+            .venues
+            .iter()
+            .map(|venue| venue.exact_amount.parse::<Decimal>().unwrap())
+            .sum();
+        assert_eq!(shared.exact_amount, total.to_string());
+    }
+
+    #[test]
+    fn test_fee_adjusted_summary() {
+        let binance_order_book = get_binance_order_book_builder().build();
+        let bitstamp_order_book = get_bitstamp_order_book_builder().build();
+        let taker_fees = TakerFees::default().with_exchange("binance".into(), dec!(10));
+        let merged_book = MergedBook::new(
+            [&binance_order_book, &bitstamp_order_book].into_iter(),
+            vec![],
+        )
+        .with_taker_fees(&taker_fees);
+
+        // raw prices by default, with the adjusted ones alongside
+        let summary = merged_book.get_summary(&SummaryRequest::default());
+        assert_eq!(summary.bids[0].exchange, "binance");
+        assert_eq!(summary.bids[0].exact_price, "0.068426");
+        assert_eq!(summary.bids[0].exact_adjusted_price, "0.068357574");
+        assert_eq!(summary.asks[0].exact_adjusted_price, "0.068495427");
+
+        // the binance fee makes bitstamp the best venue on both sides
+        let summary = merged_book.get_summary(&SummaryRequest {
+            fee_adjusted: true,
+            ..Default::default()
+        });
+        assert_eq!(summary.bids[0].exchange, "bitstamp");
+        assert_eq!(summary.asks[0].exchange, "bitstamp");
+        assert!(summary
+            .bids
+            .windows(2)
+            .all(|levels| levels[0].adjusted_price >= levels[1].adjusted_price));
+        assert!(summary
+            .asks
+            .windows(2)
+            .all(|levels| levels[0].adjusted_price <= levels[1].adjusted_price));
+        assert_eq!(summary.exact_spread, "0.00000739");
+    }
+
+    #[test]
+    fn test_consolidate_with_fees() {
+        let order_book = |exchange: &'static str, price: Decimal, amount: Decimal| {
+            let mut order_book = OrderBook::new(exchange);
+            order_book.add(Side::Bid, price, amount);
+            order_book
+        };
+        let order_books = [
+            order_book("binance", dec!(100), dec!(1)),
+            order_book("kraken", dec!(100), dec!(2)),
+            order_book("bitstamp", dec!(100), dec!(3)),
+            order_book("coinbase", dec!(99.9), dec!(4)),
+        ];
+        let taker_fees = TakerFees::default()
+            .with_exchange("binance".into(), dec!(10))
+            .with_exchange("kraken".into(), dec!(26));
+        let merged_book = MergedBook::new(order_books.iter(), vec![]).with_taker_fees(&taker_fees);
+        let get_levels = |fee_adjusted: bool| {
+            let summary = merged_book.get_summary(&SummaryRequest {
+                consolidated: true,
+                fee_adjusted,
+                ..Default::default()
+            });
+            summary
+                .bids
+                .iter()
+                .map(|level| {
+                    let venues: Vec<&str> = level
+                        .venues
+                        .iter()
+                        .map(|venue| venue.exchange.as_str())
+                        .collect();
+                    (
+                        level.exact_price.clone(),
+                        level.exact_amount.clone(),
+                        venues.join(","),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // the raw price is shared whatever the fees
+        assert_eq!(
+            get_levels(false),
+            vec![
+                (
+                    "100".to_string(),
+                    "6".to_string(),
+                    "bitstamp,kraken,binance".to_string()
+                ),
+                ("99.9".to_string(), "4".to_string(), "coinbase".to_string()),
+            ]
+        );
+        // binance pays its fee down to the coinbase price, and the levels are priced once the
+        // fees are paid
+        assert_eq!(
+            get_levels(true),
+            vec![
+                ("100".to_string(), "3".to_string(), "bitstamp".to_string()),
+                (
+                    "99.9".to_string(),
+                    "5".to_string(),
+                    "binance,coinbase".to_string()
+                ),
+                ("99.74".to_string(), "2".to_string(), "kraken".to_string()),
+            ]
+        );
+    }
+}
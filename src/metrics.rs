@@ -0,0 +1,151 @@
+use anyhow::Result;
+use metrics::{
+    counter, decrement_gauge, describe_counter, describe_gauge, describe_histogram, gauge,
+    histogram, increment_counter, increment_gauge, Unit,
+};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder};
+use std::{net::SocketAddr, time::Duration};
+
+const MESSAGES_RECEIVED: &str = "orderbook_messages_received_total";
+const PARSE_FAILURES: &str = "orderbook_parse_failures_total";
+const RECONNECTS: &str = "orderbook_reconnects_total";
+const EVENT_TO_BROADCAST: &str = "orderbook_event_to_broadcast_seconds";
+const SPREAD: &str = "orderbook_spread";
+const SUBSCRIBERS: &str = "orderbook_subscribers";
+const LAGGED: &str = "orderbook_lagged_total";
+const LAGGED_MESSAGES: &str = "orderbook_lagged_messages_total";
+
+// from a millisecond to a few seconds, exchange clocks can be a bit ahead or far behind
+const EVENT_TO_BROADCAST_BUCKETS: [f64; 12] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+// serves the metrics on http://<address>/metrics, they are dropped until it is called
+pub fn install(address: SocketAddr) -> Result<()> {
+    PrometheusBuilder::new()
+        .with_http_listener(address)
+        .set_buckets_for_metric(
+            Matcher::Full(EVENT_TO_BROADCAST.into()),
+            &EVENT_TO_BROADCAST_BUCKETS,
+        )?
+        .install()?;
+    describe();
+    Ok(())
+}
+
+fn describe() {
+    describe_counter!(MESSAGES_RECEIVED, "Websocket text frames received");
+    describe_counter!(
+        PARSE_FAILURES,
+        "Websocket frames that should have been book updates but could not be parsed"
+    );
+    describe_counter!(
+        RECONNECTS,
+        "Reconnections after a connection closed or failed"
+    );
+    describe_histogram!(
+        EVENT_TO_BROADCAST,
+        Unit::Seconds,
+        "Time from the exchange event to the merged book broadcast"
+    );
+    describe_gauge!(SPREAD, "Spread of the last merged book, before fees");
+    describe_gauge!(SUBSCRIBERS, "Active BookSummary and BookDeltas streams");
+    describe_counter!(LAGGED, "Times a stream fell behind its broadcast channel");
+    describe_counter!(
+        LAGGED_MESSAGES,
+        "Broadcast messages skipped by streams that fell behind"
+    );
+}
+
+pub fn record_message(exchange: &'static str, pair: &str) {
+    increment_counter!(MESSAGES_RECEIVED, "exchange" => exchange, "pair" => pair.to_string());
+}
+
+pub fn record_parse_failure(exchange: &'static str, pair: &str) {
+    increment_counter!(PARSE_FAILURES, "exchange" => exchange, "pair" => pair.to_string());
+}
+
+pub fn record_reconnect(exchange: &'static str, pair: &str) {
+    increment_counter!(RECONNECTS, "exchange" => exchange, "pair" => pair.to_string());
+}
+
+pub fn record_event_to_broadcast(exchange: &'static str, pair: &str, latency: Duration) {
+    histogram!(
+        EVENT_TO_BROADCAST,
+        latency.as_secs_f64(),
+        "exchange" => exchange,
+        "pair" => pair.to_string()
+    );
+}
+
+pub fn set_spread(pair: &str, spread: f64) {
+    gauge!(SPREAD, spread, "pair" => pair.to_string());
+}
+
+// counts a summary stream for as long as it lives
+pub struct SubscriberGauge(String);
+
+impl SubscriberGauge {
+    pub fn new(pair: &str) -> Self {
+        increment_gauge!(SUBSCRIBERS, 1.0, "pair" => pair.to_string());
+        Self(pair.into())
+    }
+}
+
+impl Drop for SubscriberGauge {
+    fn drop(&mut self) {
+        decrement_gauge!(SUBSCRIBERS, 1.0, "pair" => self.0.clone());
+    }
+}
+
+// the stream is what fell behind, like summary or arbitrage
+pub fn record_lagged(stream: &'static str, pair: &str, skipped: u64) {
+    increment_counter!(LAGGED, "stream" => stream, "pair" => pair.to_string());
+    counter!(LAGGED_MESSAGES, skipped, "stream" => stream, "pair" => pair.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metrics_exporter_prometheus::PrometheusBuilder;
+
+    #[test]
+    fn test_metrics() {
+        let handle = PrometheusBuilder::new()
+            .set_buckets_for_metric(
+                Matcher::Full(EVENT_TO_BROADCAST.into()),
+                &EVENT_TO_BROADCAST_BUCKETS,
+            )
+            .unwrap()
+            .install_recorder()
+            .unwrap();
+
+        record_message("binance", "ltcusd");
+        record_message("binance", "ltcusd");
+        record_parse_failure("kraken", "ltcusd");
+        record_event_to_broadcast("binance", "ltcusd", Duration::from_millis(3));
+        set_spread("ltcusd", 0.5);
+        let first = SubscriberGauge::new("ltcusd");
+        let second = SubscriberGauge::new("ltcusd");
+        drop(first);
+        record_lagged("summary", "ltcusd", 7);
+
+        let rendered = handle.render();
+        let has = |line: &str| rendered.lines().any(|rendered| rendered == line);
+        assert!(has(
+            r#"orderbook_messages_received_total{exchange="binance",pair="ltcusd"} 2"#
+        ));
+        assert!(has(
+            r#"orderbook_parse_failures_total{exchange="kraken",pair="ltcusd"} 1"#
+        ));
+        assert!(has(
+            r#"orderbook_event_to_broadcast_seconds_bucket{exchange="binance",pair="ltcusd",le="0.005"} 1"#
+        ));
+        assert!(has(r#"orderbook_spread{pair="ltcusd"} 0.5"#));
+        assert!(has(r#"orderbook_subscribers{pair="ltcusd"} 1"#));
+        assert!(has(
+            r#"orderbook_lagged_messages_total{stream="summary",pair="ltcusd"} 7"#
+        ));
+        drop(second);
+    }
+}
@@ -0,0 +1,246 @@
+use crate::{
+    merged_book::BASIS_POINTS,
+    order_book::to_f64,
+    proto::{DepthBand, Summary, SummaryRequest, Vwap},
+};
+use rust_decimal::{prelude::FromPrimitive, Decimal};
+
+const TWO: Decimal = Decimal::from_parts(2, 0, 0, false, 0);
+// depth bands and vwap notionals a single request can ask for
+pub const MAX_MICROSTRUCTURE_VALUES: usize = 20;
+
+// the metrics fields of a summary, from the (price, amount) of each level with the best price first
+pub fn get_microstructure(
+    bids: &[(Decimal, Decimal)],
+    asks: &[(Decimal, Decimal)],
+    request: &SummaryRequest,
+) -> Summary {
+    let (best_bid, best_ask) = match (get_top_of_book(bids), get_top_of_book(asks)) {
+        (Some(best_bid), Some(best_ask)) => (best_bid, best_ask),
+        _ => {
+            return Summary {
+                mid_price: f64::NAN,
+                microprice: f64::NAN,
+                imbalance: f64::NAN,
+                depth_bands: request
+                    .depth_bands_bps
+                    .iter()
+                    .map(|bps| DepthBand {
+                        bps: *bps,
+                        ..Default::default()
+                    })
+                    .collect(),
+                vwaps: request
+                    .vwap_notionals
+                    .iter()
+                    .map(|notional| Vwap {
+                        notional: *notional,
+                        bid_price: f64::NAN,
+                        ask_price: f64::NAN,
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            }
+        }
+    };
+    let ((bid_price, bid_amount), (ask_price, ask_amount)) = (best_bid, best_ask);
+
+    // halving first keeps the sum of two large prices in range
+    let mid_price = (bid_price / TWO + ask_price / TWO).normalize();
+    let (microprice, imbalance) = match bid_amount.checked_add(ask_amount) {
+        Some(top_amount) if top_amount.is_zero() => (Some(mid_price), Some(Decimal::ZERO)),
+        Some(top_amount) => (
+            get_microprice(best_bid, best_ask, top_amount),
+            Some(((bid_amount - ask_amount) / top_amount).normalize()),
+        ),
+        None => (None, None),
+    };
+
+    let depth_bands = request
+        .depth_bands_bps
+        .iter()
+        .map(|bps| {
+            // a band too wide to compute covers the whole book
+            let distance = Decimal::from_f64(*bps)
+                .and_then(|bps| mid_price.checked_mul(bps))
+                .map(|distance| distance / BASIS_POINTS);
+            let bid_amount = get_amount_while(bids, |price| {
+                distance.is_none_or(|distance| price >= mid_price - distance)
+            });
+            let ask_amount = get_amount_while(asks, |price| {
+                distance
+                    .and_then(|distance| mid_price.checked_add(distance))
+                    .is_none_or(|highest_price| price <= highest_price)
+            });
+            DepthBand {
+                bps: *bps,
+                bid_amount: to_f64(bid_amount),
+                ask_amount: to_f64(ask_amount),
+                exact_bid_amount: bid_amount.to_string(),
+                exact_ask_amount: ask_amount.to_string(),
+            }
+        })
+        .collect();
+
+    let vwaps = request
+        .vwap_notionals
+        .iter()
+        .map(|notional| {
+            let vwap = |levels| {
+                Decimal::from_f64(*notional).and_then(|notional| get_vwap(levels, notional))
+            };
+            let (bid_price, ask_price) = (vwap(bids), vwap(asks));
+            Vwap {
+                notional: *notional,
+                bid_price: bid_price.map_or(f64::NAN, to_f64),
+                ask_price: ask_price.map_or(f64::NAN, to_f64),
+                exact_bid_price: bid_price.map(|price| price.to_string()).unwrap_or_default(),
+                exact_ask_price: ask_price.map(|price| price.to_string()).unwrap_or_default(),
+            }
+        })
+        .collect();
+
+    Summary {
+        mid_price: to_f64(mid_price),
+        exact_mid_price: mid_price.to_string(),
+        microprice: microprice.map_or(f64::NAN, to_f64),
+        exact_microprice: microprice
+            .map(|price| price.to_string())
+            .unwrap_or_default(),
+        imbalance: imbalance.map_or(f64::NAN, to_f64),
+        depth_bands,
+        vwaps,
+        ..Default::default()
+    }
+}
+
+// the best price with the amount of every exchange quoting it
+fn get_top_of_book(levels: &[(Decimal, Decimal)]) -> Option<(Decimal, Decimal)> {
+    let (best_price, _) = *levels.first()?;
+    Some((
+        best_price,
+        get_amount_while(levels, |price| price == best_price),
+    ))
+}
+
+// the top prices weighted by the amount on the other side, none when it doesn't fit a decimal
+fn get_microprice(
+    (bid_price, bid_amount): (Decimal, Decimal),
+    (ask_price, ask_amount): (Decimal, Decimal),
+    top_amount: Decimal,
+) -> Option<Decimal> {
+    let weighted = bid_price
+        .checked_mul(ask_amount)?
+        .checked_add(ask_price.checked_mul(bid_amount)?)?;
+    Some((weighted / top_amount).normalize())
+}
+
+fn get_amount_while(levels: &[(Decimal, Decimal)], predicate: impl Fn(Decimal) -> bool) -> Decimal {
+    levels
+        .iter()
+        .take_while(|(price, _)| predicate(*price))
+        .map(|(_, amount)| *amount)
+        .sum::<Decimal>()
+        .normalize()
+}
+
+// average price of trading the notional against the levels, none when they are too thin
+fn get_vwap(levels: &[(Decimal, Decimal)], notional: Decimal) -> Option<Decimal> {
+    let (mut notional_left, mut amount) = (notional, Decimal::ZERO);
+    for (price, level_amount) in levels {
+        // a level worth more than a decimal holds fills the rest
+        match price.checked_mul(*level_amount) {
+            Some(level_notional) if level_notional < notional_left => {
+                notional_left -= level_notional;
+                amount = amount.checked_add(*level_amount)?;
+            }
+            _ => {
+                amount = amount.checked_add(notional_left.checked_div(*price)?)?;
+                return notional.checked_div(amount).map(|price| price.normalize());
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_get_microstructure() {
+        let bids = [
+            (dec!(99), dec!(1)),
+            (dec!(99), dec!(2)),
+            (dec!(98), dec!(4)),
+            (dec!(90), dec!(10)),
+        ];
+        let asks = [
+            (dec!(101), dec!(1)),
+            (dec!(102), dec!(2)),
+            (dec!(110), dec!(5)),
+        ];
+        let request = SummaryRequest {
+            depth_bands_bps: vec![50.0, 250.0],
+            vwap_notionals: vec![99.0, 305.0, 10000.0],
+            ..Default::default()
+        };
+
+        let summary = get_microstructure(&bids, &asks, &request);
+        assert_eq!(summary.exact_mid_price, "100");
+        // more bids at the top pull the microprice towards the ask
+        assert_eq!(summary.exact_microprice, "100.5");
+        assert_eq!(summary.imbalance, 0.5);
+
+        let bands: Vec<(&str, &str)> = summary
+            .depth_bands
+            .iter()
+            .map(|band| {
+                (
+                    band.exact_bid_amount.as_str(),
+                    band.exact_ask_amount.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(bands, vec![("0", "0"), ("7", "3")]);
+
+        assert_eq!(summary.vwaps[0].exact_bid_price, "99");
+        assert_eq!(summary.vwaps[0].exact_ask_price, "101");
+        assert_eq!(
+            summary.vwaps[1].exact_ask_price,
+            "101.66666666666666666666666667"
+        );
+        assert!(summary.vwaps[2].bid_price.is_nan());
+        assert_eq!(summary.vwaps[2].exact_ask_price, "");
+
+        let summary = get_microstructure(&bids, &[], &request);
+        assert!(summary.mid_price.is_nan());
+        assert_eq!(summary.depth_bands.len(), 2);
+        assert!(summary.vwaps[0].ask_price.is_nan());
+    }
+
+    #[test]
+    fn test_get_microstructure_out_of_range() {
+        let bids = [(Decimal::MAX / dec!(2), dec!(10))];
+        let asks = [(Decimal::MAX / dec!(2), dec!(10)), (Decimal::MAX, dec!(1))];
+        let request = SummaryRequest {
+            depth_bands_bps: vec![1e20],
+            vwap_notionals: vec![1e20],
+            ..Default::default()
+        };
+
+        let summary = get_microstructure(&bids, &asks, &request);
+        // the top levels are worth more than a decimal holds
+        assert!(summary.microprice.is_nan());
+        assert_eq!(summary.imbalance, 0.0);
+        // the band is wider than the book
+        assert_eq!(summary.depth_bands[0].exact_bid_amount, "10");
+        assert_eq!(summary.depth_bands[0].exact_ask_amount, "11");
+        // the first level fills the notional
+        assert!(summary.vwaps[0]
+            .exact_bid_price
+            .starts_with("396140812571321"));
+    }
+}
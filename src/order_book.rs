@@ -1,43 +1,203 @@
 use crate::{exchange::Exchange, proto::Level};
+use rust_decimal::Decimal;
 use serde::{
     de::{self, Error, SeqAccess, Visitor},
     Deserialize, Deserializer,
 };
-use std::{fmt, marker::PhantomData};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    marker::PhantomData,
+    str::FromStr,
+    time::{Instant, SystemTime},
+};
 #[derive(Deserialize)]
 pub struct OrderBookBuilder<X: Exchange> {
     pub bids: Vec<LevelBuilder<X>>,
     pub asks: Vec<LevelBuilder<X>>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Bid,
+    Ask,
+}
+
+// a level of an exchange book, ordered by price then amount
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PriceLevel {
+    pub price: Decimal,
+    pub amount: Decimal,
+    pub exchange: &'static str,
+}
+
+impl From<PriceLevel> for Level {
+    fn from(level: PriceLevel) -> Self {
+        Level {
+            exchange: level.exchange.into(),
+            price: to_f64(level.price),
+            amount: to_f64(level.amount),
+            exact_price: level.price.to_string(),
+            exact_amount: level.amount.to_string(),
+            venues: vec![],
+            // without fees
+            adjusted_price: to_f64(level.price),
+            exact_adjusted_price: level.price.to_string(),
+        }
+    }
+}
+
+// goes through the decimal string so the double is the closest one to the exact value
+pub fn to_f64(decimal: Decimal) -> f64 {
+    decimal.to_string().parse().unwrap_or(f64::NAN)
+}
+
+// full depth book of an exchange, both sides are keyed by price
+#[derive(Clone, Debug)]
 pub struct OrderBook {
     exchange: &'static str,
-    bids: Vec<Level>,
-    asks: Vec<Level>,
+    bids: BTreeMap<Decimal, Decimal>,
+    asks: BTreeMap<Decimal, Decimal>,
+    received_at: Instant,
+    // wall clock times for latency measurements, the instant is for staleness
+    received_time: SystemTime,
+    // when the exchange produced the book, if it says so
+    event_time: Option<SystemTime>,
 }
 
 impl OrderBook {
+    pub fn new(exchange: &'static str) -> Self {
+        Self {
+            exchange,
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            received_at: Instant::now(),
+            received_time: SystemTime::now(),
+            event_time: None,
+        }
+    }
     pub fn get_exchange_name(&self) -> &'static str {
         self.exchange
     }
-    pub fn get_levels(&self) -> (Vec<Level>, Vec<Level>) {
-        (self.bids.clone(), self.asks.clone())
+    pub fn get_received_at(&self) -> Instant {
+        self.received_at
+    }
+    pub fn set_received_at(&mut self, received_at: Instant) {
+        self.received_at = received_at;
+    }
+    pub fn get_received_time(&self) -> SystemTime {
+        self.received_time
+    }
+    pub fn set_received_time(&mut self, received_time: SystemTime) {
+        self.received_time = received_time;
+    }
+    pub fn get_event_time(&self) -> Option<SystemTime> {
+        self.event_time
+    }
+    pub fn set_event_time(&mut self, event_time: SystemTime) {
+        self.event_time = Some(event_time);
+    }
+    // insert or update a price level, a zero amount deletes it
+    pub fn update(&mut self, side: Side, price: Decimal, amount: Decimal) {
+        let levels = match side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks,
+        };
+        if amount.is_zero() {
+            levels.remove(&price);
+        } else {
+            levels.insert(price, amount);
+        }
+    }
+    // adds to the amount of a price level, for books built from several sources
+    pub fn add(&mut self, side: Side, price: Decimal, amount: Decimal) {
+        let levels = match side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks,
+        };
+        *levels.entry(price).or_default() += amount;
+    }
+    pub fn apply<X: Exchange>(&mut self, bids: &[LevelBuilder<X>], asks: &[LevelBuilder<X>]) {
+        for bid in bids {
+            self.update(Side::Bid, bid.price, bid.amount);
+        }
+        for ask in asks {
+            self.update(Side::Ask, ask.price, ask.amount);
+        }
+    }
+    // only keep the best levels of each side
+    pub fn truncate(&mut self, depth: usize) {
+        while self.bids.len() > depth {
+            self.bids.pop_first();
+        }
+        while self.asks.len() > depth {
+            self.asks.pop_last();
+        }
+    }
+    // a copy of the best levels of each side, the deeper ones are never merged
+    pub fn get_top(&self, depth: usize) -> OrderBook {
+        OrderBook {
+            exchange: self.exchange,
+            bids: self
+                .bids
+                .iter()
+                .rev()
+                .take(depth)
+                .map(|(price, amount)| (*price, *amount))
+                .collect(),
+            asks: self
+                .asks
+                .iter()
+                .take(depth)
+                .map(|(price, amount)| (*price, *amount))
+                .collect(),
+            received_at: self.received_at,
+            received_time: self.received_time,
+            event_time: self.event_time,
+        }
+    }
+    #[cfg(test)]
+    pub fn best_bid(&self) -> Option<PriceLevel> {
+        self.bids().next()
+    }
+    #[cfg(test)]
+    pub fn best_ask(&self) -> Option<PriceLevel> {
+        self.asks().next()
+    }
+    // highest price first
+    pub fn bids(&self) -> impl Iterator<Item = PriceLevel> + '_ {
+        self.bids
+            .iter()
+            .rev()
+            .map(|(price, amount)| self.level(price, amount))
+    }
+    // lowest price first
+    pub fn asks(&self) -> impl Iterator<Item = PriceLevel> + '_ {
+        self.asks
+            .iter()
+            .map(|(price, amount)| self.level(price, amount))
+    }
+    #[cfg(test)]
+    pub fn get_levels(&self, depth: usize) -> (Vec<Level>, Vec<Level>) {
+        (
+            self.bids().take(depth).map(Level::from).collect(),
+            self.asks().take(depth).map(Level::from).collect(),
+        )
+    }
+    fn level(&self, price: &Decimal, amount: &Decimal) -> PriceLevel {
+        PriceLevel {
+            price: *price,
+            amount: *amount,
+            exchange: self.exchange,
+        }
     }
 }
 
 impl<X: Exchange> OrderBookBuilder<X> {
-    pub fn build(self) -> OrderBook {
-        let OrderBookBuilder { mut bids, mut asks } = self;
-
-        bids.sort_by(|a, b| b.price.partial_cmp(&a.price).unwrap());
-        asks.sort_by(|a, b| a.price.partial_cmp(&b.price).unwrap());
-
-        OrderBook {
-            exchange: X::get_name(),
-            bids: bids.into_iter().take(10).map(|b| b.build()).collect(),
-            asks: asks.into_iter().take(10).map(|a| a.build()).collect(),
-        }
+    pub fn build(&self) -> OrderBook {
+        let mut order_book = OrderBook::new(X::get_name());
+        order_book.apply(&self.bids, &self.asks);
+        order_book
     }
 }
 
@@ -45,23 +205,13 @@ impl<X: Exchange> OrderBookBuilder<X> {
 // https://serde.rs/string-or-struct.html
 #[derive(PartialEq)]
 pub struct LevelBuilder<X: Exchange> {
-    price: f64,
-    amount: f64,
+    price: Decimal,
+    amount: Decimal,
     phantom: PhantomData<X>,
 }
 
 impl<X: Exchange> LevelBuilder<X> {
-    pub fn build(self) -> Level {
-        Level {
-            exchange: X::get_name().into(),
-            price: self.price,
-            amount: self.amount,
-        }
-    }
-}
-
-impl<X: Exchange> LevelBuilder<X> {
-    pub fn new(price: f64, amount: f64) -> Self {
+    pub fn new(price: Decimal, amount: Decimal) -> Self {
         Self {
             price,
             amount,
@@ -87,8 +237,8 @@ impl<'de, X: Exchange> Visitor<'de> for LevelVisitor<X> {
         let maybe_amount = seq.next_element::<&str>()?;
 
         if let (Some(price), Some(amount)) = (maybe_price, maybe_amount) {
-            let price = price.parse::<f64>().map_err(Error::custom)?;
-            let amount = amount.parse::<f64>().map_err(Error::custom)?;
+            let price = Decimal::from_str(price).map_err(Error::custom)?;
+            let amount = Decimal::from_str(amount).map_err(Error::custom)?;
             Ok(LevelBuilder::new(price, amount))
         } else {
             Err(de::Error::custom("Expected a array with two elements"))
@@ -113,21 +263,89 @@ mod tests {
         bitstamp::Bitstamp,
         test_data::{get_binance_order_book_builder, get_bitstamp_order_book_builder},
     };
+    use rust_decimal_macros::dec;
 
     #[test]
     fn test_deserialize_level_builder() {
         let level_builder: LevelBuilder<Bitstamp> =
             serde_json::from_str("[\"1.0\", \"2.0\"]").unwrap();
-        assert_eq!(level_builder.price, 1.0);
-        assert_eq!(level_builder.amount, 2.0);
+        assert_eq!(level_builder.price, dec!(1.0));
+        assert_eq!(level_builder.amount, dec!(2.0));
+    }
+
+    #[test]
+    fn test_level_exact_representation() {
+        let level_builder: LevelBuilder<Binance> =
+            serde_json::from_str("[\"0.06795500\", \"20.71540000\"]").unwrap();
+        let order_book = OrderBookBuilder {
+            bids: vec![level_builder],
+            asks: vec![],
+        }
+        .build();
+        let level = Level::from(order_book.best_bid().unwrap());
+
+        // the exchange representation is kept as is next to the double
+        assert_eq!(level.exact_price, "0.06795500");
+        assert_eq!(level.exact_amount, "20.71540000");
+        assert_eq!(level.price, 0.067955);
+        assert_eq!(level.amount, 20.7154);
     }
 
     #[test]
     fn test_level_builder() {
-        let level_builder: LevelBuilder<Bitstamp> = LevelBuilder::new(1.0, 2.0);
-        let level = level_builder.build();
-        assert_eq!(level.price, 1.0);
-        assert_eq!(level.amount, 2.0);
+        let level_builder: LevelBuilder<Bitstamp> = LevelBuilder::new(dec!(1.0), dec!(2.0));
+        let order_book = OrderBookBuilder {
+            bids: vec![level_builder],
+            asks: vec![],
+        }
+        .build();
+        let level = order_book.best_bid().unwrap();
+        assert_eq!(level.exchange, "bitstamp");
+        assert_eq!(level.price, dec!(1.0));
+        assert_eq!(level.amount, dec!(2.0));
+    }
+
+    #[test]
+    fn test_order_book() {
+        let mut order_book = OrderBook::new("binance");
+        assert!(order_book.best_bid().is_none());
+        assert!(order_book.best_ask().is_none());
+
+        order_book.update(Side::Bid, dec!(0.9), dec!(2.0));
+        order_book.update(Side::Bid, dec!(1.0), dec!(1.0));
+        order_book.update(Side::Ask, dec!(1.1), dec!(1.0));
+
+        // insert
+        order_book.update(Side::Bid, dec!(0.95), dec!(3.0));
+        // update
+        order_book.update(Side::Ask, dec!(1.1), dec!(4.0));
+        // delete
+        order_book.update(Side::Bid, dec!(1.0), dec!(0.0));
+        // deleting an unknown price is a no-op
+        order_book.update(Side::Ask, dec!(1.2), dec!(0.0));
+
+        let best_bid = order_book.best_bid().unwrap();
+        assert_eq!(best_bid.exchange, "binance");
+        assert_eq!(best_bid.price, dec!(0.95));
+        assert_eq!(best_bid.amount, dec!(3.0));
+        assert_eq!(order_book.best_ask().unwrap().amount, dec!(4.0));
+
+        let (bids, asks) = order_book.get_levels(10);
+        assert_eq!(bids.len(), 2);
+        assert_eq!(bids[1].price, 0.9);
+        assert_eq!(asks.len(), 1);
+
+        let (bids, _) = order_book.get_levels(1);
+        assert_eq!(bids.len(), 1);
+
+        // the worst levels are dropped
+        order_book.update(Side::Ask, dec!(1.3), dec!(1.0));
+        order_book.truncate(1);
+        let (bids, asks) = order_book.get_levels(10);
+        assert_eq!(bids.len(), 1);
+        assert_eq!(bids[0].price, 0.95);
+        assert_eq!(asks.len(), 1);
+        assert_eq!(asks[0].price, 1.1);
     }
 
     #[test]
@@ -139,16 +357,19 @@ mod tests {
 
         assert_eq!(order_book.exchange, "bitstamp");
 
-        assert_eq!(order_book.bids.len(), 10);
-        assert_eq!(order_book.asks.len(), 10);
+        // the full depth is kept
+        assert_eq!(order_book.bids.len(), 100);
+        assert_eq!(order_book.asks.len(), 100);
+
+        let (bids, asks) = order_book.get_levels(10);
 
         // highest price first for bids
-        assert_eq!(order_book.bids[0].price, 0.06842268);
-        assert_eq!(order_book.bids[9].price, 0.06839848);
+        assert_eq!(bids[0].price, 0.06842268);
+        assert_eq!(bids[9].price, 0.06839848);
 
         // lowest price first for asks
-        assert_eq!(order_book.asks[0].price, 0.06843007);
-        assert_eq!(order_book.asks[9].price, 0.06847844);
+        assert_eq!(asks[0].price, 0.06843007);
+        assert_eq!(asks[9].price, 0.06847844);
 
         // Binance
         let order_book_builder: OrderBookBuilder<Binance> = get_binance_order_book_builder();
@@ -160,12 +381,33 @@ mod tests {
         assert_eq!(order_book.bids.len(), 10);
         assert_eq!(order_book.asks.len(), 10);
 
+        let (bids, asks) = order_book.get_levels(10);
+
         // highest price first for bids
-        assert_eq!(order_book.bids[0].price, 0.068426);
-        assert_eq!(order_book.bids[9].price, 0.068416);
+        assert_eq!(bids[0].price, 0.068426);
+        assert_eq!(bids[9].price, 0.068416);
 
         // lowest price first for asks
-        assert_eq!(order_book.asks[0].price, 0.068427);
-        assert_eq!(order_book.asks[9].price, 0.068438);
+        assert_eq!(asks[0].price, 0.068427);
+        assert_eq!(asks[9].price, 0.068438);
+    }
+
+    #[test]
+    fn test_get_top() {
+        let mut order_book = OrderBook::new("binance");
+        for price in 1..=5 {
+            order_book.update(Side::Bid, Decimal::from(price), dec!(1));
+            order_book.update(Side::Ask, Decimal::from(price + 5), dec!(1));
+        }
+        order_book.set_event_time(SystemTime::UNIX_EPOCH);
+
+        let top = order_book.get_top(2);
+        let prices = |levels: Vec<PriceLevel>| -> Vec<Decimal> {
+            levels.into_iter().map(|level| level.price).collect()
+        };
+        assert_eq!(prices(top.bids().collect()), vec![dec!(5), dec!(4)]);
+        assert_eq!(prices(top.asks().collect()), vec![dec!(6), dec!(7)]);
+        assert_eq!(top.get_event_time(), Some(SystemTime::UNIX_EPOCH));
+        assert_eq!(order_book.bids().count(), 5);
     }
 }
@@ -1,7 +1,12 @@
+// shared by the server and client binaries, each of which only uses part of it
+#[allow(unused_imports)]
 pub use orderbook::{
     orderbook_aggregator_client::OrderbookAggregatorClient,
     orderbook_aggregator_server::{OrderbookAggregator, OrderbookAggregatorServer},
-    Empty, Level, Summary,
+    ArbitrageEvent, ArbitrageRequest, ArbitrageStatus, BookDelta, ConnectionState, DepthBand,
+    Empty, ExchangeConnection, ExchangeConnections, ExchangeTimes, FillEstimate, FillRequest,
+    Level, LevelChange, LevelDelta, OrderSide, Side, Summary, SummaryRequest, VenueAmount,
+    VenueFill, Vwap,
 };
 
 mod orderbook {
@@ -0,0 +1,355 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use flate2::{read::MultiGzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::task::spawn_blocking;
+use tracing::{error, info};
+
+const DEFAULT_MAX_FILE_MB: u64 = 100;
+const DEFAULT_ROTATE_AFTER_MINUTES: u64 = 60;
+const CAPTURE_EXTENSION: &str = "jsonl.gz";
+
+// where the captures go and when a new file is started
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RecorderSettings {
+    pub directory: PathBuf,
+    // compressed size
+    pub max_file_mb: u64,
+    pub rotate_after_minutes: u64,
+}
+
+impl Default for RecorderSettings {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("captures"),
+            max_file_mb: DEFAULT_MAX_FILE_MB,
+            rotate_after_minutes: DEFAULT_ROTATE_AFTER_MINUTES,
+        }
+    }
+}
+
+impl RecorderSettings {
+    #[cfg(test)]
+    pub fn new(directory: PathBuf) -> Self {
+        Self {
+            directory,
+            ..Self::default()
+        }
+    }
+}
+
+// where a captured frame comes from
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FrameSource {
+    // a websocket text frame
+    #[default]
+    Websocket,
+    // the body of a rest snapshot, which the diff streams are applied on
+    Rest,
+}
+
+// a raw frame as it was received, one json line of a capture file
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct CapturedFrame {
+    // nanoseconds since the unix epoch
+    pub received_at_ns: u64,
+    pub exchange: String,
+    pub pair: String,
+    // captures without it only have websocket frames
+    #[serde(default)]
+    pub source: FrameSource,
+    pub frame: String,
+}
+
+impl CapturedFrame {
+    #[cfg(test)]
+    pub fn get_received_time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_nanos(self.received_at_ns)
+    }
+}
+
+// what the recorders send to the writer
+enum Capture {
+    Frame(CapturedFrame),
+    Stop,
+}
+
+// writes the frames of every connection it is given to from a thread of its own, so the exchange
+// tasks never wait for the disk
+#[derive(Clone)]
+pub struct Recorder {
+    sender: Sender<Capture>,
+    writer: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+impl Recorder {
+    pub fn new(settings: RecorderSettings) -> Result<Self> {
+        fs::create_dir_all(&settings.directory).with_context(|| {
+            format!(
+                "can't create the capture directory {}",
+                settings.directory.display()
+            )
+        })?;
+        let (sender, receiver) = channel();
+        let writer = thread::Builder::new()
+            .name("recorder".into())
+            .spawn(move || write_captures(settings, receiver))?;
+        Ok(Self {
+            sender,
+            writer: Arc::new(Mutex::new(Some(writer))),
+        })
+    }
+    // takes the receive time right away, the frame is written later
+    pub fn record(&self, exchange: &str, pair: &str, source: FrameSource, frame: &str) {
+        let received_at_ns = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_nanos() as u64);
+        // the frames recorded once the writer is stopped are dropped
+        let _ = self.sender.send(Capture::Frame(CapturedFrame {
+            received_at_ns,
+            exchange: exchange.into(),
+            pair: pair.into(),
+            source,
+            frame: frame.into(),
+        }));
+    }
+    // writes the frames recorded so far and closes the file, even while connections still hold
+    // clones of the recorder
+    pub async fn stop(self) {
+        let _ = self.sender.send(Capture::Stop);
+        let writer = self.writer.lock().unwrap().take();
+        if let Some(writer) = writer {
+            let _ = spawn_blocking(move || writer.join()).await;
+        }
+    }
+}
+
+// the capture file being written
+struct CaptureFile {
+    path: PathBuf,
+    encoder: GzEncoder<File>,
+    opened_at: Instant,
+}
+
+impl CaptureFile {
+    fn open(directory: &Path) -> Result<Self> {
+        let opened_time: DateTime<Utc> = SystemTime::now().into();
+        let path = directory.join(format!(
+            "capture-{}.{}",
+            opened_time.format("%Y%m%dT%H%M%S%.6fZ"),
+            CAPTURE_EXTENSION
+        ));
+        // appending to an existing capture adds a gzip member, which the reader handles
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("can't open the capture file {}", path.display()))?;
+        info!(path = %path.display(), "capture file opened");
+        Ok(Self {
+            path,
+            encoder: GzEncoder::new(file, Compression::default()),
+            opened_at: Instant::now(),
+        })
+    }
+    fn write(&mut self, frame: &CapturedFrame) -> Result<()> {
+        serde_json::to_writer(&mut self.encoder, frame)?;
+        self.encoder.write_all(b"\n")?;
+        Ok(())
+    }
+    fn is_full(&self, settings: &RecorderSettings) -> Result<bool> {
+        let size = self.encoder.get_ref().metadata()?.len();
+        Ok(size >= settings.max_file_mb * 1024 * 1024
+            || self.opened_at.elapsed() >= Duration::from_secs(settings.rotate_after_minutes * 60))
+    }
+    // writes the gzip trailer
+    fn close(self) -> Result<()> {
+        self.encoder.finish()?.sync_all()?;
+        info!(path = %self.path.display(), "capture file closed");
+        Ok(())
+    }
+}
+
+fn write_captures(settings: RecorderSettings, receiver: Receiver<Capture>) {
+    let mut file: Option<CaptureFile> = None;
+    let mut stopped = false;
+    while !stopped {
+        // blocks until a frame comes in, then writes whatever else is waiting before flushing
+        let mut frames = match receiver.recv() {
+            Ok(Capture::Frame(frame)) => vec![frame],
+            // stopped, or every recorder is gone
+            Ok(Capture::Stop) | Err(_) => break,
+        };
+        for capture in receiver.try_iter() {
+            match capture {
+                Capture::Frame(frame) => frames.push(frame),
+                Capture::Stop => {
+                    stopped = true;
+                    break;
+                }
+            }
+        }
+        if let Err(error) = write_batch(&settings, &mut file, &frames) {
+            // the frames are lost until the next file can be opened
+            error!(%error, "can't write the capture");
+            file = None;
+        }
+    }
+    if let Some(file) = file {
+        if let Err(error) = file.close() {
+            error!(%error, "can't close the capture");
+        }
+    }
+}
+
+fn write_batch(
+    settings: &RecorderSettings,
+    file: &mut Option<CaptureFile>,
+    frames: &[CapturedFrame],
+) -> Result<()> {
+    let capture = match file {
+        Some(capture) => capture,
+        None => file.insert(CaptureFile::open(&settings.directory)?),
+    };
+    for frame in frames {
+        capture.write(frame)?;
+    }
+    // a crash only loses the frames of the current batch
+    capture.encoder.flush()?;
+    if capture.is_full(settings)? {
+        if let Some(capture) = file.take() {
+            capture.close()?;
+        }
+    }
+    Ok(())
+}
+
+// the capture files of a directory, oldest first, or a single file
+pub fn get_capture_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = fs::read_dir(path)
+        .with_context(|| format!("can't read the captures in {}", path.display()))?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>>>()?;
+    files.retain(|file| file.to_string_lossy().ends_with(CAPTURE_EXTENSION));
+    // the names start with the time the file was opened
+    files.sort();
+    Ok(files)
+}
+
+// the frames of a capture file in the order they were received
+pub fn read_captures(path: &Path) -> Result<impl Iterator<Item = Result<CapturedFrame>>> {
+    let file = File::open(path)
+        .with_context(|| format!("can't open the capture file {}", path.display()))?;
+    let path = path.to_path_buf();
+    Ok(BufReader::new(MultiGzDecoder::new(file))
+        .lines()
+        // a file that is still being written has no gzip trailer yet, its last line can be cut
+        .map_while(Result::ok)
+        .filter(|line| !line.is_empty())
+        .map(move |line| {
+            serde_json::from_str(&line)
+                .with_context(|| format!("invalid frame in {}", path.display()))
+        }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use tokio::time::sleep;
+
+    fn get_directory(name: &str) -> PathBuf {
+        let directory = temp_dir().join(format!("recorder-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    fn read_all(directory: &Path) -> Vec<CapturedFrame> {
+        get_capture_files(directory)
+            .unwrap()
+            .iter()
+            .flat_map(|file| read_captures(file).unwrap())
+            .collect::<Result<_>>()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_recorder() {
+        let directory = get_directory("frames");
+        let recorder = Recorder::new(RecorderSettings::new(directory.clone())).unwrap();
+        recorder.record(
+            "binance",
+            "ethbtc",
+            FrameSource::Rest,
+            r#"{"lastUpdateId":1}"#,
+        );
+        recorder.clone().record(
+            "bitstamp",
+            "ethbtc",
+            FrameSource::Websocket,
+            "{\"event\":\"data\"}\n",
+        );
+        // a clone still held by a connection doesn't keep the file open
+        let connection_recorder = recorder.clone();
+        recorder.stop().await;
+        connection_recorder.record("binance", "ethbtc", FrameSource::Websocket, "dropped");
+
+        let frames = read_all(&directory);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(
+            (frames[0].exchange.as_str(), frames[0].frame.as_str()),
+            ("binance", r#"{"lastUpdateId":1}"#)
+        );
+        assert_eq!(frames[0].source, FrameSource::Rest);
+        assert_eq!(frames[1].frame, "{\"event\":\"data\"}\n");
+        assert_eq!(frames[1].source, FrameSource::Websocket);
+        assert!(frames[0].received_at_ns <= frames[1].received_at_ns);
+        assert!(frames[0].get_received_time() > UNIX_EPOCH);
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rotation() {
+        let directory = get_directory("rotation");
+        // every batch fills a file
+        let recorder = Recorder::new(RecorderSettings {
+            max_file_mb: 0,
+            ..RecorderSettings::new(directory.clone())
+        })
+        .unwrap();
+        for sequence in 0..3 {
+            recorder.record(
+                "binance",
+                "ethbtc",
+                FrameSource::Websocket,
+                &sequence.to_string(),
+            );
+            sleep(Duration::from_millis(50)).await;
+        }
+        recorder.stop().await;
+
+        assert_eq!(get_capture_files(&directory).unwrap().len(), 3);
+        let frames: Vec<String> = read_all(&directory)
+            .into_iter()
+            .map(|frame| frame.frame)
+            .collect();
+        assert_eq!(frames, vec!["0", "1", "2"]);
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
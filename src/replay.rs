@@ -0,0 +1,403 @@
+use crate::{
+    connection::Connection,
+    exchange::{Frame, SnapshotFrames},
+    recorder::{get_capture_files, read_captures, CapturedFrame, FrameSource},
+};
+use anyhow::{anyhow, Result};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+use tokio::{
+    sync::mpsc::{channel, Sender},
+    task::spawn_blocking,
+    time::sleep_until,
+};
+use tracing::{debug, error, info, warn};
+
+// frames read ahead of the replay
+const REPLAY_BUFFER_SIZE: usize = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReplaySpeed {
+    // 1 is the original speed, 2 twice as fast
+    Scaled(f64),
+    // as fast as the order books are taken
+    Max,
+}
+
+impl Default for ReplaySpeed {
+    fn default() -> Self {
+        ReplaySpeed::Scaled(1.0)
+    }
+}
+
+impl FromStr for ReplaySpeed {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        if value.eq_ignore_ascii_case("max") {
+            return Ok(ReplaySpeed::Max);
+        }
+        match value.parse::<f64>() {
+            Ok(speed) if speed.is_finite() && speed > 0.0 => Ok(ReplaySpeed::Scaled(speed)),
+            _ => Err(anyhow!(
+                "the replay speed must be max or a positive factor, got {}",
+                value
+            )),
+        }
+    }
+}
+
+impl Display for ReplaySpeed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplaySpeed::Scaled(speed) => write!(f, "{}", speed),
+            ReplaySpeed::Max => write!(f, "max"),
+        }
+    }
+}
+
+// capture files to play instead of connecting the exchanges
+#[derive(Clone, Debug)]
+pub struct Replay {
+    path: PathBuf,
+    speed: ReplaySpeed,
+}
+
+impl Replay {
+    // a capture file or a directory of them
+    pub fn new(path: &Path) -> Result<Self> {
+        if !path.exists() || get_capture_files(path)?.is_empty() {
+            return Err(anyhow!("no capture file in {}", path.display()));
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            speed: ReplaySpeed::default(),
+        })
+    }
+    pub fn with_speed(self, speed: ReplaySpeed) -> Self {
+        Self { speed, ..self }
+    }
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+    pub fn get_speed(&self) -> ReplaySpeed {
+        self.speed
+    }
+    pub fn session(&self, pair: &str) -> ReplaySession {
+        ReplaySession {
+            replay: self.clone(),
+            pair: pair.into(),
+            exchanges: HashMap::new(),
+        }
+    }
+}
+
+// parses the frames of an exchange in the order they were received
+type ParseFrame = Box<dyn FnMut(FrameSource, &str) -> Result<Frame> + Send>;
+
+struct ReplayedExchange {
+    connection: Connection,
+    parse_frame: ParseFrame,
+}
+
+// the replay of a pair, it plays the frames of the exchanges it is given and skips the others
+pub struct ReplaySession {
+    replay: Replay,
+    pair: String,
+    exchanges: HashMap<&'static str, ReplayedExchange>,
+}
+
+impl ReplaySession {
+    pub fn with_exchange<X: SnapshotFrames>(self, connection: Connection) -> Self {
+        self.with_parser(X::get_name(), connection, |_, frame| X::parse_frame(frame))
+    }
+    // for the exchanges whose frames depend on the ones before, like the binance diff stream
+    pub fn with_parser(
+        mut self,
+        exchange: &'static str,
+        connection: Connection,
+        parse_frame: impl FnMut(FrameSource, &str) -> Result<Frame> + Send + 'static,
+    ) -> Self {
+        self.exchanges.insert(
+            exchange,
+            ReplayedExchange {
+                connection,
+                parse_frame: Box::new(parse_frame),
+            },
+        );
+        self
+    }
+    // sends the order books of the frames like the exchange tasks do, until the captures end or
+    // nobody listens anymore
+    pub async fn run(mut self) {
+        let (sender, mut receiver) = channel(REPLAY_BUFFER_SIZE);
+        let path = self.replay.path.clone();
+        let pair = self.pair.clone();
+        spawn_blocking(move || {
+            if let Err(error) = read_frames(&path, &pair, sender) {
+                error!(%error, "can't read the captures");
+            }
+        });
+
+        info!(pair = %self.pair, speed = %self.replay.speed, "replay started");
+        let started_at = Instant::now();
+        let mut first_received_at_ns = None;
+        let mut frames = 0;
+        while let Some(frame) = receiver.recv().await {
+            let exchange = match self.exchanges.get_mut(frame.exchange.as_str()) {
+                Some(exchange) => exchange,
+                None => continue,
+            };
+            // the frames keep the time between them, scaled by the speed
+            if let ReplaySpeed::Scaled(speed) = self.replay.speed {
+                let first = *first_received_at_ns.get_or_insert(frame.received_at_ns);
+                let offset = Duration::from_nanos(frame.received_at_ns.saturating_sub(first));
+                sleep_until((started_at + offset.div_f64(speed)).into()).await;
+            }
+
+            let connection = &exchange.connection;
+            connection.record_message(&frame.frame);
+            match (exchange.parse_frame)(frame.source, &frame.frame) {
+                Ok(Frame::Book(order_book)) => {
+                    if connection.send(order_book).await.is_err() {
+                        debug!(pair = %self.pair, "replay stopped, nobody listens anymore");
+                        return;
+                    }
+                }
+                Ok(_) => {}
+                Err(error) => {
+                    connection.record_parse_failure();
+                    warn!(%error, exchange = %frame.exchange, "can't replay a frame");
+                }
+            }
+            frames += 1;
+        }
+        // the last books stay in the summary until they are stale
+        info!(pair = %self.pair, frames, "replay finished");
+    }
+}
+
+// runs on a blocking thread, the captures are read as the replay goes
+fn read_frames(path: &Path, pair: &str, sender: Sender<CapturedFrame>) -> Result<()> {
+    for file in get_capture_files(path)? {
+        for frame in read_captures(&file)? {
+            let frame = frame?;
+            if frame.pair == pair && sender.blocking_send(frame).is_err() {
+                // the replay is over
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        binance::{Binance, BinanceCaptureFrames},
+        bitstamp::Bitstamp,
+        connection::ConnectionStates,
+        exchange::Exchange,
+        order_book::OrderBook,
+        test_data::{
+            get_binance_diff_depth_gap_update, get_binance_diff_depth_updates,
+            get_binance_resync_snapshot_response, get_binance_snapshot_response,
+            get_binance_stale_resync_snapshot_response, get_binance_websocket_response,
+            get_bitstamp_websocket_response,
+        },
+    };
+    use flate2::{write::GzEncoder, Compression};
+    use rust_decimal_macros::dec;
+    use std::{env::temp_dir, fs::File, io::Write};
+    use tokio::sync::mpsc;
+
+    // a binance frame every 100 ms, then a bitstamp one and a frame of another pair
+    fn write_capture(name: &str) -> PathBuf {
+        let websocket = FrameSource::Websocket;
+        write_frames(
+            name,
+            &[
+                (
+                    0,
+                    "binance",
+                    "ethbtc",
+                    websocket,
+                    get_binance_websocket_response(),
+                ),
+                (
+                    100,
+                    "binance",
+                    "ethbtc",
+                    websocket,
+                    "{\"result\":null,\"id\":1}",
+                ),
+                (
+                    200,
+                    "binance",
+                    "ethbtc",
+                    websocket,
+                    get_binance_websocket_response(),
+                ),
+                (
+                    300,
+                    "bitstamp",
+                    "ethbtc",
+                    websocket,
+                    get_bitstamp_websocket_response(),
+                ),
+                (
+                    300,
+                    "binance",
+                    "btcusdt",
+                    websocket,
+                    get_binance_websocket_response(),
+                ),
+            ],
+        )
+    }
+
+    fn write_frames(name: &str, frames: &[(u64, &str, &str, FrameSource, &str)]) -> PathBuf {
+        let path = temp_dir().join(format!("replay-{}-{}.jsonl.gz", name, std::process::id()));
+        let mut encoder = GzEncoder::new(File::create(&path).unwrap(), Compression::default());
+        for (offset_ms, exchange, pair, source, frame) in frames {
+            let frame = CapturedFrame {
+                received_at_ns: 1_682_167_286_000_000_000 + offset_ms * 1_000_000,
+                exchange: exchange.to_string(),
+                pair: pair.to_string(),
+                source: *source,
+                frame: frame.to_string(),
+            };
+            serde_json::to_writer(&mut encoder, &frame).unwrap();
+            encoder.write_all(b"\n").unwrap();
+        }
+        encoder.finish().unwrap();
+        path
+    }
+
+    async fn replay(path: &Path, speed: ReplaySpeed) -> Vec<OrderBook> {
+        let (sender, mut receiver) = mpsc::channel(100);
+        let states = ConnectionStates::default();
+        let connection = |exchange| Connection::new(exchange, sender.clone(), states.clone());
+        let mut binance_frames = BinanceCaptureFrames::default();
+        Replay::new(path)
+            .unwrap()
+            .with_speed(speed)
+            .session("ethbtc")
+            .with_parser(
+                Binance::get_name(),
+                connection(Binance::get_name()),
+                move |source, frame| binance_frames.parse_frame(source, frame),
+            )
+            .with_exchange::<Bitstamp>(connection(Bitstamp::get_name()))
+            .run()
+            .await;
+        drop(sender);
+
+        let mut order_books = vec![];
+        while let Some(order_book) = receiver.recv().await {
+            order_books.push(order_book);
+        }
+        order_books
+    }
+
+    #[test]
+    fn test_replay_speed() {
+        assert_eq!("max".parse::<ReplaySpeed>().unwrap(), ReplaySpeed::Max);
+        assert_eq!(
+            "2.5".parse::<ReplaySpeed>().unwrap(),
+            ReplaySpeed::Scaled(2.5)
+        );
+        assert!("0".parse::<ReplaySpeed>().is_err());
+        assert!("fast".parse::<ReplaySpeed>().is_err());
+        assert!(Replay::new(Path::new("/nonexistent/capture.jsonl.gz")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_replay() {
+        let path = write_capture("max");
+        let started_at = Instant::now();
+        let order_books = replay(&path, ReplaySpeed::Max).await;
+        assert!(started_at.elapsed() < Duration::from_millis(100));
+
+        // the subscription confirmation and the other pair are skipped
+        let exchanges: Vec<&str> = order_books
+            .iter()
+            .map(OrderBook::get_exchange_name)
+            .collect();
+        assert_eq!(exchanges, vec!["binance", "binance", "bitstamp"]);
+        assert_eq!(order_books[0].best_bid().unwrap().price, dec!(0.067955));
+        assert!(order_books[2].get_event_time().is_some());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_replay_scaled() {
+        let path = write_capture("scaled");
+        let started_at = Instant::now();
+        let order_books = replay(&path, ReplaySpeed::Scaled(2.0)).await;
+        // 300 ms of frames at twice the speed
+        let elapsed = started_at.elapsed();
+        assert!(elapsed >= Duration::from_millis(150) && elapsed < Duration::from_millis(300));
+        assert_eq!(order_books.len(), 3);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_replay_diff() {
+        // a diff stream capture with a gap that a second snapshot fixes
+        let (rest, websocket) = (FrameSource::Rest, FrameSource::Websocket);
+        let [first, second, third] = get_binance_diff_depth_updates();
+        let path = write_frames(
+            "diff",
+            &[
+                (
+                    0,
+                    "binance",
+                    "ethbtc",
+                    rest,
+                    get_binance_snapshot_response(),
+                ),
+                (0, "binance", "ethbtc", websocket, first),
+                (0, "binance", "ethbtc", websocket, second),
+                (0, "binance", "ethbtc", websocket, third),
+                (
+                    0,
+                    "binance",
+                    "ethbtc",
+                    websocket,
+                    get_binance_diff_depth_gap_update(),
+                ),
+                (
+                    0,
+                    "binance",
+                    "ethbtc",
+                    rest,
+                    get_binance_stale_resync_snapshot_response(),
+                ),
+                (
+                    0,
+                    "binance",
+                    "ethbtc",
+                    rest,
+                    get_binance_resync_snapshot_response(),
+                ),
+            ],
+        );
+        let order_books = replay(&path, ReplaySpeed::Max).await;
+
+        // the snapshot, the two updates after it and the gap update on the second snapshot
+        assert_eq!(order_books.len(), 4);
+        assert_eq!(order_books[0].best_bid().unwrap().price, dec!(0.067955));
+        assert_eq!(order_books[1].best_bid().unwrap().price, dec!(0.067954));
+        assert_eq!(order_books[2].best_ask().unwrap().price, dec!(0.0679555));
+        assert_eq!(order_books[3].best_bid().unwrap().price, dec!(0.06796));
+        assert_eq!(order_books[3].best_ask().unwrap().price, dec!(0.068));
+        std::fs::remove_file(path).unwrap();
+    }
+}
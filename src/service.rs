@@ -1,25 +1,50 @@
 use crate::{
-    binance::Binance,
-    bitstamp::Bitstamp,
-    exchange::Exchange,
-    order_book::OrderBook,
-    proto::{Empty, Level, OrderbookAggregator, Summary},
+    aggregator::{
+        AggregatorSettings, ArbitrageSubscription, PairAggregator, StalenessTimeouts, Subscription,
+    },
+    binance::BinanceStream,
+    connection::ConnectionState,
+    delta::get_book_delta,
+    exchange::ExchangeSettings,
+    fill::{get_fill_estimate, get_order_size},
+    instrument::Instrument,
+    merged_book::{TakerFees, MAX_DEPTH},
+    metrics::{self, SubscriberGauge},
+    microstructure::MAX_MICROSTRUCTURE_VALUES,
+    proto::{
+        self, ArbitrageEvent, ArbitrageRequest, BookDelta, Empty, ExchangeConnection,
+        ExchangeConnections, FillEstimate, FillRequest, OrderbookAggregator, Summary,
+        SummaryRequest,
+    },
+    recorder::Recorder,
+    replay::Replay,
+    synthetic::SyntheticPair,
 };
-use core::cmp::Ordering;
 use futures_util::{
     ready,
     task::{Context, Poll},
     Stream,
 };
-use std::{collections::HashMap, marker::PhantomData, pin::Pin, sync::Arc};
-use tokio::{
-    spawn,
-    sync::{broadcast, broadcast::error::RecvError, mpsc, Mutex},
+use rust_decimal::{prelude::FromPrimitive, Decimal};
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
+use tokio::{select, sync::broadcast::error::RecvError, time::sleep_until};
 use tokio_util::sync::ReusableBoxFuture;
+use tracing::{info, info_span, trace, warn, Instrument as _, Span};
 
 use tonic::{Request, Response, Status};
-const CHANNEL_BUFFER_SIZE: usize = 100;
+
+// how long a unary call waits for the first merged book of a pair nobody streams
+const FIRST_MERGED_BOOK_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[derive(Clone)]
 pub struct Connected;
@@ -28,138 +53,269 @@ pub struct Connected;
 pub struct NotConnected;
 
 pub struct OrderBookService<ServiceStatus = NotConnected> {
-    pair: String,
-    exchanges: Arc<Mutex<HashMap<&'static str, OrderBook>>>,
+    instruments: Vec<Instrument>,
+    settings: AggregatorSettings,
+    synthetic_pairs: Vec<SyntheticPair>,
+    aggregators: BTreeMap<String, Arc<PairAggregator>>,
     status: PhantomData<ServiceStatus>,
-    summary_sender: Option<broadcast::Sender<Summary>>,
 }
 
 impl OrderBookService {
-    pub fn new(pair: String) -> Self {
+    pub fn new(instruments: Vec<Instrument>) -> Self {
         Self {
-            pair,
-            exchanges: Arc::new(Mutex::new(HashMap::new())),
+            instruments,
+            settings: AggregatorSettings::default(),
+            synthetic_pairs: vec![],
+            aggregators: BTreeMap::new(),
             status: PhantomData,
-            summary_sender: None,
         }
     }
+    pub fn with_binance_stream(mut self, binance_stream: BinanceStream) -> Self {
+        self.settings.binance_stream = binance_stream;
+        self
+    }
+    pub fn with_staleness_timeouts(mut self, staleness_timeouts: StalenessTimeouts) -> Self {
+        self.settings.staleness_timeouts = staleness_timeouts;
+        self
+    }
+    pub fn with_taker_fees(mut self, taker_fees: TakerFees) -> Self {
+        self.settings.taker_fees = taker_fees;
+        self
+    }
+    pub fn with_exchange_settings(mut self, exchange: String, settings: ExchangeSettings) -> Self {
+        self.settings.exchanges.insert(exchange, settings);
+        self
+    }
+    pub fn with_channel_buffer_size(mut self, channel_buffer_size: usize) -> Self {
+        self.settings.channel_buffer_size = channel_buffer_size;
+        self
+    }
+    pub fn with_recorder(mut self, recorder: Option<Recorder>) -> Self {
+        self.settings.recorder = recorder;
+        self
+    }
+    pub fn with_replay(mut self, replay: Option<Replay>) -> Self {
+        self.settings.replay = replay;
+        self
+    }
+    pub fn with_synthetic_pairs(self, synthetic_pairs: Vec<SyntheticPair>) -> Self {
+        Self {
+            synthetic_pairs,
+            ..self
+        }
+    }
+    // the exchanges of a pair are only connected while it has subscribers
     pub fn connect_exchanges(self) -> OrderBookService<Connected> {
-        let exchanges: Arc<Mutex<HashMap<&str, OrderBook>>> = self.get_exchanges();
-
-        let (order_book_tx, mut order_book_rx) = mpsc::channel::<OrderBook>(CHANNEL_BUFFER_SIZE);
-        let (summary_tx, _summary_rx) = broadcast::channel::<Summary>(CHANNEL_BUFFER_SIZE);
-
-        spawn(Bitstamp::get_order_book(
-            self.pair.clone(),
-            order_book_tx.clone(),
-        ));
-        spawn(Binance::get_order_book(self.pair.clone(), order_book_tx));
-
-        let exchanges_clone = exchanges.clone();
-        let summary_tx_clone = summary_tx.clone();
-
-        spawn(async move {
-            while let Some(order_book) = order_book_rx.recv().await {
-                update_exchange(&exchanges_clone, order_book).await;
-
-                if summary_tx_clone
-                    .send(get_summary(&exchanges_clone).await)
-                    .is_ok()
-                {
-                    println!("Summary sent")
-                }
-            }
-        });
+        let mut aggregators = BTreeMap::new();
+        // the legs of synthetic pairs are aggregated too, even when they are not configured
+        let legs = self
+            .synthetic_pairs
+            .iter()
+            .flat_map(|synthetic_pair| synthetic_pair.get_legs());
+        for instrument in self.instruments.iter().chain(legs) {
+            aggregators.entry(instrument.get_pair()).or_insert_with(|| {
+                Arc::new(PairAggregator::new(
+                    instrument.clone(),
+                    self.settings.clone(),
+                ))
+            });
+        }
+        for synthetic_pair in &self.synthetic_pairs {
+            let [first, second] = synthetic_pair
+                .get_legs()
+                .map(|instrument| aggregators[&instrument.get_pair()].clone());
+            aggregators.insert(
+                synthetic_pair.get_symbol(),
+                Arc::new(PairAggregator::new_synthetic(
+                    synthetic_pair.clone(),
+                    [first, second],
+                    self.settings.clone(),
+                )),
+            );
+        }
 
         OrderBookService {
-            pair: self.pair,
-            exchanges,
+            instruments: self.instruments,
+            settings: self.settings,
+            synthetic_pairs: self.synthetic_pairs,
+            aggregators,
             status: PhantomData,
-            summary_sender: Some(summary_tx),
         }
     }
-    fn get_exchanges(&self) -> Arc<Mutex<HashMap<&'static str, OrderBook>>> {
-        self.exchanges.clone()
-    }
 }
 
-impl PartialOrd for Level {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        if self.price == other.price {
-            return Some(self.amount.partial_cmp(&other.amount).unwrap());
+// the status is large, but it is what the rpcs return anyway
+#[allow(clippy::result_large_err)]
+impl OrderBookService<Connected> {
+    fn get_aggregator(&self, symbol: &str) -> Result<&Arc<PairAggregator>, Status> {
+        // a server aggregating a single pair doesn't need the symbol
+        if symbol.is_empty() && self.aggregators.len() == 1 {
+            return Ok(self.aggregators.values().next().unwrap());
+        }
+        self.aggregators
+            .get(&symbol.to_lowercase())
+            .ok_or_else(|| Status::not_found(format!("{} is not aggregated", symbol)))
+    }
+    fn get_summary_aggregator(
+        &self,
+        request: &SummaryRequest,
+    ) -> Result<&Arc<PairAggregator>, Status> {
+        if request.depth as usize > MAX_DEPTH {
+            return Err(Status::invalid_argument(format!(
+                "depth can't be greater than {}",
+                MAX_DEPTH
+            )));
+        }
+        if request.depth_bands_bps.len() > MAX_MICROSTRUCTURE_VALUES
+            || request.vwap_notionals.len() > MAX_MICROSTRUCTURE_VALUES
+        {
+            return Err(Status::invalid_argument(format!(
+                "at most {} depth bands and {} vwap notionals",
+                MAX_MICROSTRUCTURE_VALUES, MAX_MICROSTRUCTURE_VALUES
+            )));
         }
-        Some(self.price.partial_cmp(&other.price).unwrap())
+        if request
+            .depth_bands_bps
+            .iter()
+            .chain(&request.vwap_notionals)
+            .any(|value| *value <= 0.0 || Decimal::from_f64(*value).is_none())
+        {
+            return Err(Status::invalid_argument(
+                "depth bands and vwap notionals must be positive decimals",
+            ));
+        }
+        self.get_aggregator(&request.symbol)
+    }
+    // a summary stream of every aggregated pair, which keeps their exchanges connected
+    pub fn subscribe_summaries(
+        &self,
+        request: &SummaryRequest,
+    ) -> Vec<(String, OrderBookSummaryStream)> {
+        self.aggregators
+            .iter()
+            .map(|(pair, aggregator)| {
+                let stream = OrderBookSummaryStream::new(aggregator.subscribe(), request.clone());
+                (pair.clone(), stream)
+            })
+            .collect()
     }
 }
 
-async fn update_exchange(exchanges: &Arc<Mutex<HashMap<&str, OrderBook>>>, order_book: OrderBook) {
-    let mut exchanges = exchanges.lock().await;
-    let order_book_exchange_name = order_book.get_exchange_name();
-    // if key exists, update value to avoid cloning
-    if exchanges.contains_key(order_book_exchange_name) {
-        exchanges
-            .entry(order_book_exchange_name)
-            .and_modify(|e| *e = order_book);
-    } else {
-        exchanges
-            .entry(order_book_exchange_name)
-            .or_insert(order_book);
-    }
+fn no_merged_book(pair: &str) -> Status {
+    Status::unavailable(format!("no exchange sent a book for {} yet", pair))
 }
 
-fn get_ten_first_levels(levels: Vec<Level>) -> Vec<Level> {
-    levels.into_iter().take(10).collect()
+fn to_exchange_connection(
+    symbol: &str,
+    exchange: &str,
+    state: ConnectionState,
+) -> ExchangeConnection {
+    let (state, attempt) = match state {
+        ConnectionState::Connecting => (proto::ConnectionState::Connecting, 0),
+        ConnectionState::Connected => (proto::ConnectionState::Connected, 0),
+        ConnectionState::Reconnecting { attempt } => {
+            (proto::ConnectionState::Reconnecting, attempt)
+        }
+        ConnectionState::Failed => (proto::ConnectionState::Failed, 0),
+    };
+    ExchangeConnection {
+        exchange: exchange.into(),
+        state: state.into(),
+        attempt,
+        symbol: symbol.into(),
+    }
 }
 
-async fn merge_levels(
-    exchanges: &Arc<Mutex<HashMap<&'static str, OrderBook>>>,
-) -> (Vec<Level>, Vec<Level>) {
-    let (mut bids, mut asks) = exchanges.lock().await.values().fold(
-        (vec![], vec![]),
-        |(mut acc_bids, mut acc_asks), order_book| {
-            let (mut bids, mut asks) = order_book.get_levels();
-            acc_bids.append(&mut bids);
-            acc_asks.append(&mut asks);
-            (acc_bids, acc_asks)
-        },
+// tells the logs of concurrent subscribers apart
+static SUBSCRIBER_ID: AtomicU64 = AtomicU64::new(0);
+
+fn get_subscriber_span(stream: &'static str, pair: &str) -> Span {
+    let span = info_span!(
+        "subscriber",
+        id = SUBSCRIBER_ID.fetch_add(1, Ordering::Relaxed) + 1,
+        stream,
+        pair = %pair
     );
+    span.in_scope(|| info!("subscribed"));
+    span
+}
 
-    bids.sort_by(|a, b| b.partial_cmp(a).unwrap());
-    asks.sort_by(|a, b| a.partial_cmp(b).unwrap());
+// what a single BookSummary subscriber asked for
+struct Subscriber {
+    subscription: Subscription,
+    request: SummaryRequest,
+    last_sent_at: Option<Instant>,
+    span: Span,
+    _gauge: SubscriberGauge,
+}
 
-    (get_ten_first_levels(bids), get_ten_first_levels(asks))
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        self.span.in_scope(|| info!("unsubscribed"));
+    }
 }
 
-async fn get_summary(exchanges: &Arc<Mutex<HashMap<&'static str, OrderBook>>>) -> Summary {
-    let (merged_bids, merged_asks) = merge_levels(exchanges).await;
+pub struct OrderBookSummaryStream {
+    inner: ReusableBoxFuture<'static, (Option<Result<Summary, Status>>, Subscriber)>,
+}
 
-    let spread = match (merged_asks.first(), merged_bids.first()) {
-        (Some(first_ask), Some(first_bid)) => first_ask.price - first_bid.price,
-        _ => f64::NAN,
+async fn make_future(mut subscriber: Subscriber) -> (Option<Result<Summary, Status>>, Subscriber) {
+    let mut merged_book = match subscriber.subscription.recv().await {
+        Ok(merged_book) => merged_book,
+        Err(RecvError::Closed) => return (None, subscriber),
+        Err(RecvError::Lagged(skipped)) => {
+            warn!(skipped, "subscriber lagged");
+            metrics::record_lagged("summary", subscriber.subscription.get_pair(), skipped);
+            return (Some(Err(Status::internal("Message lagged"))), subscriber);
+        }
     };
 
-    Summary {
-        spread,
-        bids: merged_bids,
-        asks: merged_asks,
+    // a throttled subscriber only gets the latest merged book once its interval elapsed
+    if let (Some(last_sent_at), min_interval_ms @ 1..) =
+        (subscriber.last_sent_at, subscriber.request.min_interval_ms)
+    {
+        let publish_at = last_sent_at + Duration::from_millis(min_interval_ms);
+        let publish = sleep_until(publish_at.into());
+        tokio::pin!(publish);
+        loop {
+            select! {
+                _ = &mut publish => break,
+                result = subscriber.subscription.recv() => match result {
+                    Ok(latest) => merged_book = latest,
+                    // the skipped books would have been conflated anyway
+                    Err(RecvError::Lagged(skipped)) => {
+                        metrics::record_lagged("summary", subscriber.subscription.get_pair(), skipped);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+            }
+        }
     }
-}
 
-pub struct OrderBookSummaryStream {
-    inner: ReusableBoxFuture<'static, (Result<Summary, RecvError>, broadcast::Receiver<Summary>)>,
+    subscriber.last_sent_at = Some(Instant::now());
+    let summary = merged_book.get_summary(&subscriber.request);
+    trace!(sequence = summary.sequence, "summary sent");
+    (Some(Ok(summary)), subscriber)
 }
 
-async fn make_future(
-    mut rx: broadcast::Receiver<Summary>,
-) -> (Result<Summary, RecvError>, broadcast::Receiver<Summary>) {
-    let result = rx.recv().await;
-    (result, rx)
+fn next_summary(
+    subscriber: Subscriber,
+) -> impl Future<Output = (Option<Result<Summary, Status>>, Subscriber)> {
+    let span = subscriber.span.clone();
+    make_future(subscriber).instrument(span)
 }
 
 impl OrderBookSummaryStream {
-    pub fn new(summary_rx: broadcast::Receiver<Summary>) -> Self {
+    pub fn new(subscription: Subscription, request: SummaryRequest) -> Self {
         Self {
-            inner: ReusableBoxFuture::new(make_future(summary_rx)),
+            inner: ReusableBoxFuture::new(next_summary(Subscriber {
+                span: get_subscriber_span("summary", subscription.get_pair()),
+                _gauge: SubscriberGauge::new(subscription.get_pair()),
+                subscription,
+                request,
+                last_sent_at: None,
+            })),
         }
     }
 }
@@ -167,62 +323,263 @@ impl OrderBookSummaryStream {
 impl Stream for OrderBookSummaryStream {
     type Item = Result<Summary, Status>;
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let (result, rx) = ready!(self.inner.poll(cx));
-        self.inner.set(make_future(rx));
-        match result {
-            Ok(item) => Poll::Ready(Some(Ok(item))),
-            Err(RecvError::Closed) => Poll::Ready(None),
-            Err(RecvError::Lagged(_)) => Poll::Ready(Some(Err(Status::internal("Message lagged")))),
+        let (item, subscriber) = ready!(self.inner.poll(cx));
+        self.inner.set(next_summary(subscriber));
+        Poll::Ready(item)
+    }
+}
+
+// the summaries of a subscriber turned into level changes
+pub struct BookDeltaStream {
+    summaries: OrderBookSummaryStream,
+    // the last summary sent, which the client book matches
+    previous: Option<Summary>,
+}
+
+impl BookDeltaStream {
+    pub fn new(summaries: OrderBookSummaryStream) -> Self {
+        Self {
+            summaries,
+            previous: None,
+        }
+    }
+}
+
+impl Stream for BookDeltaStream {
+    type Item = Result<BookDelta, Status>;
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let summary = match ready!(Pin::new(&mut self.summaries).poll_next(cx)) {
+                Some(Ok(summary)) => summary,
+                // only lagging fails, which the next delta catches up on
+                Some(Err(_)) => continue,
+                None => return Poll::Ready(None),
+            };
+            let delta = get_book_delta(self.previous.as_ref(), &summary);
+            // none of the levels of the subscriber changed
+            if !delta.snapshot && delta.levels.is_empty() {
+                continue;
+            }
+            self.previous = Some(summary);
+            return Poll::Ready(Some(Ok(delta)));
+        }
+    }
+}
+
+pub struct ArbitrageStream {
+    span: Span,
+    inner: ReusableBoxFuture<
+        'static,
+        (
+            Option<Result<ArbitrageEvent, Status>>,
+            ArbitrageSubscription,
+        ),
+    >,
+}
+
+async fn make_arbitrage_future(
+    mut subscription: ArbitrageSubscription,
+) -> (
+    Option<Result<ArbitrageEvent, Status>>,
+    ArbitrageSubscription,
+) {
+    let item = match subscription.recv().await {
+        Ok(event) => Some(Ok(event)),
+        Err(RecvError::Closed) => None,
+        Err(RecvError::Lagged(skipped)) => {
+            warn!(skipped, "subscriber lagged");
+            metrics::record_lagged("arbitrage", subscription.get_pair(), skipped);
+            Some(Err(Status::internal("Message lagged")))
+        }
+    };
+    (item, subscription)
+}
+
+impl ArbitrageStream {
+    pub fn new(subscription: ArbitrageSubscription) -> Self {
+        let span = get_subscriber_span("arbitrage", subscription.get_pair());
+        Self {
+            inner: ReusableBoxFuture::new(
+                make_arbitrage_future(subscription).instrument(span.clone()),
+            ),
+            span,
         }
     }
 }
 
+impl Drop for ArbitrageStream {
+    fn drop(&mut self) {
+        self.span.in_scope(|| info!("unsubscribed"));
+    }
+}
+
+impl Stream for ArbitrageStream {
+    type Item = Result<ArbitrageEvent, Status>;
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let (item, subscription) = ready!(self.inner.poll(cx));
+        let span = self.span.clone();
+        self.inner
+            .set(make_arbitrage_future(subscription).instrument(span));
+        Poll::Ready(item)
+    }
+}
+
 #[tonic::async_trait]
 impl OrderbookAggregator for OrderBookService<Connected> {
     type BookSummaryStream = OrderBookSummaryStream;
+    type BookDeltasStream = BookDeltaStream;
+    type ArbitrageOpportunitiesStream = ArbitrageStream;
 
     async fn book_summary(
         &self,
-        _request: Request<Empty>,
+        request: Request<SummaryRequest>,
     ) -> Result<Response<Self::BookSummaryStream>, Status> {
-        let OrderBookService { summary_sender, .. } = self;
-        if let Some(sender) = summary_sender {
-            return Ok(Response::new(OrderBookSummaryStream::new(
-                sender.subscribe(),
-            )));
+        let request = request.into_inner();
+        let subscription = self.get_summary_aggregator(&request)?.subscribe();
+        Ok(Response::new(OrderBookSummaryStream::new(
+            subscription,
+            request,
+        )))
+    }
+
+    async fn book_deltas(
+        &self,
+        request: Request<SummaryRequest>,
+    ) -> Result<Response<Self::BookDeltasStream>, Status> {
+        let request = request.into_inner();
+        let subscription = self.get_summary_aggregator(&request)?.subscribe();
+        Ok(Response::new(BookDeltaStream::new(
+            OrderBookSummaryStream::new(subscription, request),
+        )))
+    }
+
+    async fn arbitrage_opportunities(
+        &self,
+        request: Request<ArbitrageRequest>,
+    ) -> Result<Response<Self::ArbitrageOpportunitiesStream>, Status> {
+        let request = request.into_inner();
+        let subscription = self.get_aggregator(&request.symbol)?.subscribe_arbitrage();
+        Ok(Response::new(ArbitrageStream::new(subscription)))
+    }
+
+    async fn get_summary(
+        &self,
+        request: Request<SummaryRequest>,
+    ) -> Result<Response<Summary>, Status> {
+        let request = request.into_inner();
+        let merged_book = self
+            .get_summary_aggregator(&request)?
+            .get_current_merged_book(FIRST_MERGED_BOOK_TIMEOUT)
+            .await
+            .ok_or_else(|| no_merged_book(&request.symbol))?;
+        Ok(Response::new(merged_book.get_summary(&request)))
+    }
+
+    async fn simulate_fill(
+        &self,
+        request: Request<FillRequest>,
+    ) -> Result<Response<FillEstimate>, Status> {
+        let request = request.into_inner();
+        if !request.quantity.is_finite() || !request.notional.is_finite() {
+            return Err(Status::invalid_argument(
+                "the quantity or notional must be a finite positive number",
+            ));
         }
-        Err(Status::internal("Summary stream not initialized"))
+        let is_size = |value: f64| value > 0.0;
+        if is_size(request.quantity) == is_size(request.notional)
+            || request.quantity < 0.0
+            || request.notional < 0.0
+        {
+            return Err(Status::invalid_argument(
+                "either a positive quantity or a positive notional is required",
+            ));
+        }
+        let order_size = get_order_size(&request).ok_or_else(|| {
+            Status::invalid_argument("the quantity or notional is too large for a decimal")
+        })?;
+        let merged_book = self
+            .get_aggregator(&request.symbol)?
+            .get_current_merged_book(FIRST_MERGED_BOOK_TIMEOUT)
+            .await
+            .ok_or_else(|| no_merged_book(&request.symbol))?;
+        Ok(Response::new(get_fill_estimate(
+            &merged_book,
+            &request,
+            order_size,
+        )))
+    }
+
+    async fn get_exchange_connections(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<ExchangeConnections>, Status> {
+        let connections = self
+            .aggregators
+            .values()
+            .flat_map(|aggregator| {
+                aggregator
+                    .get_connection_states()
+                    .into_iter()
+                    .map(|(exchange, state)| {
+                        to_exchange_connection(aggregator.get_pair(), exchange, state)
+                    })
+            })
+            .collect();
+        Ok(Response::new(ExchangeConnections { connections }))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_data::{get_binance_order_book_builder, get_bitstamp_order_book_builder};
+    use crate::{
+        aggregator::CHANNEL_BUFFER_SIZE,
+        merged_book::MergedBook,
+        test_data::{get_binance_order_book_builder, get_bitstamp_order_book_builder},
+    };
+    use futures_util::StreamExt;
+    use tokio::sync::broadcast;
 
     #[tokio::test]
-    async fn test_get_summary() {
-        let service = OrderBookService::new("ethbtc".into());
-        let exchanges_mutex = service.get_exchanges();
-        update_exchange(&exchanges_mutex, get_bitstamp_order_book_builder().build()).await;
-        update_exchange(&exchanges_mutex, get_binance_order_book_builder().build()).await;
-
-        let summary = get_summary(&exchanges_mutex).await;
-        assert_eq!(summary.spread, 1.000000000001e-6);
-
-        assert_eq!(summary.bids.len(), 10);
-        assert_eq!(summary.asks.len(), 10);
-
-        // highest price first for bids
-        assert_eq!(summary.bids[0].price, 0.068426);
-        assert_eq!(summary.bids[9].price, 0.06842);
-
-        // lowest price first for asks
-        assert_eq!(summary.asks[0].price, 0.068427);
-        assert_eq!(summary.asks[9].price, 0.068437);
-
-        // test that bids with price 0.06842268 are sorted by amount
-        assert!(summary.bids[3].price == summary.bids[4].price);
-        assert!(summary.bids[3].amount > summary.bids[4].amount);
+    async fn test_throttled_summary_stream() {
+        let (merged_book_tx, merged_book_rx) = broadcast::channel(CHANNEL_BUFFER_SIZE);
+        let mut stream = OrderBookSummaryStream::new(
+            Subscription::from_receiver(merged_book_rx),
+            SummaryRequest {
+                depth: 5,
+                exchanges: vec!["binance".into()],
+                min_interval_ms: 100,
+                ..Default::default()
+            },
+        );
+        let binance_order_book = get_binance_order_book_builder().build();
+        let bitstamp_order_book = get_bitstamp_order_book_builder().build();
+        let merged_book = |stale_exchanges: Vec<String>| {
+            Arc::new(MergedBook::new(
+                [&binance_order_book, &bitstamp_order_book].into_iter(),
+                stale_exchanges,
+            ))
+        };
+
+        // the first summary is sent right away
+        merged_book_tx.send(merged_book(vec![])).unwrap();
+        let summary = stream.next().await.unwrap().unwrap();
+        assert_eq!(summary.bids.len(), 5);
+        assert!(summary.bids.iter().all(|bid| bid.exchange == "binance"));
+
+        // the next ones are conflated until the interval elapsed
+        let start = Instant::now();
+        merged_book_tx
+            .send(merged_book(vec!["kraken".into()]))
+            .unwrap();
+        merged_book_tx
+            .send(merged_book(vec!["coinbase".into()]))
+            .unwrap();
+        let summary = stream.next().await.unwrap().unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(90));
+        assert_eq!(summary.stale_exchanges, vec!["coinbase"]);
+
+        drop(merged_book_tx);
+        assert!(stream.next().await.is_none());
     }
 }
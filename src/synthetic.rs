@@ -0,0 +1,319 @@
+use crate::{
+    instrument::{Instrument, Instruments},
+    merged_book::MergedBook,
+    order_book::{OrderBook, Side},
+};
+use anyhow::{anyhow, Result};
+use rust_decimal::Decimal;
+
+// exchange name of the levels of a synthetic book
+pub const SYNTHETIC_EXCHANGE: &str = "synthetic";
+
+// one of the two pairs a synthetic pair is implied from
+#[derive(Clone, Debug, PartialEq)]
+struct Leg {
+    instrument: Instrument,
+    // the leg is quoted the other way round, like btceth for eth to btc
+    inverted: bool,
+}
+
+impl Leg {
+    // the levels of a side as (price, amount) from the leg base to the leg quote, best price first
+    // levels priced at zero or out of the decimal range once inverted are skipped
+    fn get_levels(&self, merged_book: &MergedBook, side: Side) -> Vec<(Decimal, Decimal)> {
+        if !self.inverted {
+            return merged_book
+                .get_levels(side, false)
+                .iter()
+                .filter(|level| !level.price.is_zero())
+                .map(|level| (level.price, level.amount))
+                .collect();
+        }
+        // selling the base of an inverted leg is buying its quote, so the sides swap
+        let side = match side {
+            Side::Bid => Side::Ask,
+            Side::Ask => Side::Bid,
+        };
+        merged_book
+            .get_levels(side, false)
+            .iter()
+            .filter(|level| !level.price.is_zero())
+            .filter_map(|level| {
+                Some((
+                    Decimal::ONE.checked_div(level.price)?.normalize(),
+                    level.amount.checked_mul(level.price)?.normalize(),
+                ))
+            })
+            .collect()
+    }
+}
+
+// a pair no exchange lists, implied from two pairs sharing a currency like etheur from ethbtc and btceur
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyntheticPair {
+    instrument: Instrument,
+    legs: [Leg; 2],
+}
+
+impl SyntheticPair {
+    // parses etheur=ethbtc,btceur, each leg can be quoted either way
+    pub fn parse(value: &str, instruments: &Instruments) -> Result<Self> {
+        let (symbol, legs) = value
+            .split_once('=')
+            .ok_or_else(|| anyhow!("expected <symbol>=<pair>,<pair>, got {}", value))?;
+        let (first, second) = legs
+            .split_once(',')
+            .ok_or_else(|| anyhow!("expected two pairs for {}, got {}", symbol, legs))?;
+        let (first, second) = (instruments.get(first)?, instruments.get(second)?);
+        let currencies = |instrument: &Instrument| {
+            [
+                instrument.get_base().to_string(),
+                instrument.get_quote().to_string(),
+            ]
+        };
+
+        // the first leg goes from the base to a common currency, the second from it to the quote
+        let common = currencies(first)
+            .into_iter()
+            .find(|currency| currencies(second).contains(currency))
+            .ok_or_else(|| {
+                anyhow!(
+                    "{} and {} have no currency in common",
+                    first.get_pair(),
+                    second.get_pair()
+                )
+            })?;
+        let first_inverted = first.get_base() == common;
+        let second_inverted = second.get_quote() == common;
+        let base = if first_inverted {
+            first.get_quote()
+        } else {
+            first.get_base()
+        };
+        let quote = if second_inverted {
+            second.get_base()
+        } else {
+            second.get_quote()
+        };
+
+        let symbol = symbol.to_lowercase();
+        let instrument = Instrument::new(base, quote);
+        if base == quote || symbol != instrument.get_pair() {
+            return Err(anyhow!(
+                "{} and {} imply {}{}, not {}",
+                first.get_pair(),
+                second.get_pair(),
+                base,
+                quote,
+                symbol
+            ));
+        }
+
+        Ok(Self {
+            instrument,
+            legs: [
+                Leg {
+                    instrument: first.clone(),
+                    inverted: first_inverted,
+                },
+                Leg {
+                    instrument: second.clone(),
+                    inverted: second_inverted,
+                },
+            ],
+        })
+    }
+    pub fn get_symbol(&self) -> String {
+        self.instrument.get_pair()
+    }
+    pub fn get_instrument(&self) -> Instrument {
+        self.instrument.clone()
+    }
+    pub fn get_legs(&self) -> [&Instrument; 2] {
+        [&self.legs[0].instrument, &self.legs[1].instrument]
+    }
+    // the implied book, every level is what both legs can trade at once
+    pub fn get_order_book(&self, first: &MergedBook, second: &MergedBook) -> OrderBook {
+        let mut order_book = OrderBook::new(SYNTHETIC_EXCHANGE);
+        for side in [Side::Bid, Side::Ask] {
+            let levels = combine(
+                &self.legs[0].get_levels(first, side),
+                &self.legs[1].get_levels(second, side),
+            );
+            for (price, amount) in levels {
+                order_book.add(side, price, amount);
+            }
+        }
+        order_book
+    }
+}
+
+// walks both legs best price first, the first is priced in the common currency and the second in the
+// quote, the amounts of the first are in the base and the ones of the second in the common currency
+fn combine(first: &[(Decimal, Decimal)], second: &[(Decimal, Decimal)]) -> Vec<(Decimal, Decimal)> {
+    let mut levels = vec![];
+    let (mut first_index, mut second_index) = (0, 0);
+    let mut first_left = first.first().map_or(Decimal::ZERO, |(_, amount)| *amount);
+    let mut second_left = second.first().map_or(Decimal::ZERO, |(_, amount)| *amount);
+
+    while let (Some((first_price, _)), Some((second_price, _))) =
+        (first.get(first_index), second.get(second_index))
+    {
+        // the base amount the second leg can take at this price, a first leg level out of the
+        // decimal range is skipped
+        let (second_capacity, price) = match (
+            second_left.checked_div(*first_price),
+            first_price.checked_mul(*second_price),
+        ) {
+            (Some(second_capacity), Some(price)) => (second_capacity, price),
+            _ => {
+                first_index += 1;
+                first_left = first
+                    .get(first_index)
+                    .map_or(Decimal::ZERO, |(_, amount)| *amount);
+                continue;
+            }
+        };
+        let amount = if first_left <= second_capacity {
+            let amount = first_left;
+            second_left -= amount * first_price;
+            first_left = Decimal::ZERO;
+            amount
+        } else {
+            let amount = second_capacity;
+            first_left -= amount;
+            second_left = Decimal::ZERO;
+            amount
+        };
+        if !amount.is_zero() {
+            levels.push((price.normalize(), amount.normalize()));
+        }
+        if first_left.is_zero() {
+            first_index += 1;
+            first_left = first
+                .get(first_index)
+                .map_or(Decimal::ZERO, |(_, amount)| *amount);
+        }
+        if second_left.is_zero() {
+            second_index += 1;
+            second_left = second
+                .get(second_index)
+                .map_or(Decimal::ZERO, |(_, amount)| *amount);
+        }
+    }
+    levels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::SummaryRequest;
+    use rust_decimal_macros::dec;
+
+    fn get_merged_book(bids: &[(Decimal, Decimal)], asks: &[(Decimal, Decimal)]) -> MergedBook {
+        let mut order_book = OrderBook::new("binance");
+        for (price, amount) in bids {
+            order_book.update(Side::Bid, *price, *amount);
+        }
+        for (price, amount) in asks {
+            order_book.update(Side::Ask, *price, *amount);
+        }
+        MergedBook::new([&order_book].into_iter(), vec![])
+    }
+
+    fn levels(order_book: &OrderBook) -> Vec<String> {
+        let summary = MergedBook::new([order_book].into_iter(), vec![])
+            .get_summary(&SummaryRequest::default());
+        summary
+            .bids
+            .iter()
+            .map(|level| format!("bid {} {}", level.exact_price, level.exact_amount))
+            .chain(
+                summary
+                    .asks
+                    .iter()
+                    .map(|level| format!("ask {} {}", level.exact_price, level.exact_amount)),
+            )
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let instruments = Instruments::default();
+        let pair = SyntheticPair::parse("ETHEUR=ethbtc,btceur", &instruments).unwrap();
+        assert_eq!(pair.get_symbol(), "etheur");
+        let [first, second] = pair.get_legs();
+        assert_eq!(
+            (first.get_pair(), second.get_pair()),
+            ("ethbtc".into(), "btceur".into())
+        );
+        assert!(!pair.legs[0].inverted && !pair.legs[1].inverted);
+
+        let pair = SyntheticPair::parse("etheur=ethusd,eurusd", &instruments).unwrap();
+        assert!(!pair.legs[0].inverted && pair.legs[1].inverted);
+
+        assert!(SyntheticPair::parse("etheur=ethbtc,btcusd", &instruments).is_err());
+        assert!(SyntheticPair::parse("etheur=ethbtc,ethusd", &instruments).is_err());
+        assert!(SyntheticPair::parse("etheur=ethbtc", &instruments).is_err());
+    }
+
+    #[test]
+    fn test_get_order_book() {
+        let ethbtc = get_merged_book(
+            &[(dec!(0.05), dec!(2)), (dec!(0.04), dec!(10))],
+            &[(dec!(0.06), dec!(1))],
+        );
+        let btceur = get_merged_book(
+            &[(dec!(20000), dec!(0.15)), (dec!(19000), dec!(1))],
+            &[(dec!(21000), dec!(1))],
+        );
+
+        // the first btceur bid takes the first ethbtc bid and part of the next one
+        let instruments = Instruments::default();
+        let pair = SyntheticPair::parse("etheur=ethbtc,btceur", &instruments).unwrap();
+        assert_eq!(
+            levels(&pair.get_order_book(&ethbtc, &btceur)),
+            vec!["bid 1000 2", "bid 800 1.25", "bid 760 8.75", "ask 1260 1",]
+        );
+
+        // selling eth for eur buys eur with btc on the eurbtc asks
+        let eurbtc = get_merged_book(
+            &[(dec!(0.00004), dec!(21000))],
+            &[(dec!(0.00005), dec!(3000)), (dec!(0.0001), dec!(1000))],
+        );
+        let instruments = Instruments::parse(
+            r#"{
+                "ethbtc": {"base": "eth", "quote": "btc", "venues": {"binance": "ETHBTC"}},
+                "eurbtc": {"base": "eur", "quote": "btc", "venues": {"binance": "EURBTC"}}
+            }"#,
+        )
+        .unwrap();
+        let pair = SyntheticPair::parse("etheur=ethbtc,eurbtc", &instruments).unwrap();
+        assert_eq!(
+            levels(&pair.get_order_book(&ethbtc, &eurbtc)),
+            vec!["bid 1000 2", "bid 800 1.25", "bid 400 2.5", "ask 1500 1",]
+        );
+    }
+
+    #[test]
+    fn test_combine() {
+        // a level out of the decimal range is skipped instead of panicking
+        assert_eq!(
+            combine(
+                &[(Decimal::MAX, dec!(1)), (dec!(0.05), dec!(2))],
+                &[(dec!(20000), dec!(1))],
+            ),
+            vec![(dec!(1000), dec!(2))]
+        );
+
+        // zero prices are dropped on every leg
+        let ethbtc = get_merged_book(&[(dec!(0.05), dec!(2)), (dec!(0), dec!(10))], &[]);
+        let btceur = get_merged_book(&[(dec!(20000), dec!(1)), (dec!(0), dec!(1))], &[]);
+        let instruments = Instruments::default();
+        let pair = SyntheticPair::parse("etheur=ethbtc,btceur", &instruments).unwrap();
+        assert_eq!(
+            levels(&pair.get_order_book(&ethbtc, &btceur)),
+            vec!["bid 1000 2"]
+        );
+    }
+}
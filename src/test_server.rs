@@ -1,21 +1,40 @@
-use crate::order_book::OrderBook;
+use crate::{
+    connection::{Connection, ConnectionStates},
+    exchange::Exchange,
+    order_book::OrderBook,
+};
 use futures_util::{SinkExt, StreamExt};
 use std::net::SocketAddr;
 use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
     net::{TcpListener, TcpStream},
     spawn,
     sync::mpsc::{channel, Receiver, Sender},
 };
-use tokio_tungstenite::{accept_async, tungstenite::Result};
+use tokio_tungstenite::{
+    accept_async,
+    tungstenite::{Message, Result},
+};
+use tracing::debug;
 
 async fn handle_connection(
     peer: SocketAddr,
     stream: TcpStream,
     websocket_rx: &mut Receiver<String>,
+    client_tx: Sender<String>,
 ) -> Result<()> {
     let ws_stream = accept_async(stream).await.expect("Failed to accept");
-    println!("New WebSocket connection: {}", peer);
-    let (mut ws_sender, _ws_receiver) = ws_stream.split();
+    debug!(%peer, "websocket connection accepted");
+    let (mut ws_sender, mut ws_receiver) = ws_stream.split();
+
+    // keep what the client sends (subscriptions) so tests can assert on it
+    spawn(async move {
+        while let Some(Ok(message)) = ws_receiver.next().await {
+            if let Message::Text(text) = message {
+                let _ = client_tx.try_send(text);
+            }
+        }
+    });
 
     while let Some(msg) = websocket_rx.recv().await {
         ws_sender.send(msg.into()).await?;
@@ -26,6 +45,7 @@ async fn handle_connection(
 
 pub struct TestServer {
     pub websocket_tx: Sender<String>,
+    client_rx: Receiver<String>,
 }
 
 impl TestServer {
@@ -33,32 +53,88 @@ impl TestServer {
         let addr = format!("127.0.0.1:{}", port);
         let listener = TcpListener::bind(&addr).await.expect("Can't listen");
         let (websocket_tx, mut websocket_rx) = channel::<String>(100);
+        let (client_tx, client_rx) = channel::<String>(100);
 
-        println!("Listening on: {}", addr);
+        debug!(%addr, "test server listening");
 
         spawn(async move {
             while let Ok((stream, _)) = listener.accept().await {
                 let peer = stream
                     .peer_addr()
                     .expect("connected streams should have a peer address");
-                println!("Peer address: {}", peer);
+                debug!(%peer, "test client connected");
 
                 // can only accept one connection at a time
-                handle_connection(peer, stream, &mut websocket_rx)
+                handle_connection(peer, stream, &mut websocket_rx, client_tx.clone())
                     .await
                     .unwrap();
             }
         });
 
-        Self { websocket_tx }
+        Self {
+            websocket_tx,
+            client_rx,
+        }
     }
 
-    pub fn get_channels(&self) -> (Sender<OrderBook>, Receiver<OrderBook>) {
+    pub fn get_connection<X: Exchange>(&self) -> (Connection, Receiver<OrderBook>) {
         let (order_book_tx, order_book_rx) = channel::<OrderBook>(100);
-        (order_book_tx, order_book_rx)
+        let connection = Connection::new(X::get_name(), order_book_tx, ConnectionStates::default());
+        (connection, order_book_rx)
     }
 
     pub async fn send_message(&mut self, message: &str) {
         self.websocket_tx.send(message.into()).await.unwrap();
     }
+
+    pub async fn receive_message(&mut self) -> Option<String> {
+        self.client_rx.recv().await
+    }
+}
+
+// stands in for the REST endpoints of the exchanges, answers each request with the next queued body
+pub struct TestRestServer {
+    pub response_tx: Sender<String>,
+}
+
+impl TestRestServer {
+    pub async fn new(port: &str) -> Self {
+        let addr = format!("127.0.0.1:{}", port);
+        let listener = TcpListener::bind(&addr).await.expect("Can't listen");
+        let (response_tx, mut response_rx) = channel::<String>(100);
+
+        debug!(%addr, "test server listening");
+
+        spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                read_request(&mut stream).await;
+
+                if let Some(body) = response_rx.recv().await {
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    stream.write_all(response.as_bytes()).await.unwrap();
+                }
+            }
+        });
+
+        Self { response_tx }
+    }
+
+    pub async fn send_response(&mut self, body: &str) {
+        self.response_tx.send(body.into()).await.unwrap();
+    }
+}
+
+async fn read_request(stream: &mut TcpStream) {
+    let mut request = vec![];
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        match stream.read(&mut buffer).await {
+            Ok(0) | Err(_) => return,
+            Ok(read) => request.extend_from_slice(&buffer[..read]),
+        }
+    }
 }